@@ -1,11 +1,116 @@
 pub mod safe_math;
+pub mod signed;
 
+use std::fmt;
+use std::str::FromStr;
 use uint::construct_uint;
 
+// `Display` on the constructed types is decimal, while `FromStr` parses hex; decimal strings are
+// parsed with `from_dec_str`, which fails with this error, or with `U192::parse_decimal`
+pub use uint::FromDecStrErr;
+
+pub use signed::I192;
+
 construct_uint! {
     pub struct U192(3);
 }
 
+construct_uint! {
+    pub struct U256(4);
+}
+
+impl U192 {
+    /// Returns the memory representation of this integer as a byte array in little-endian order.
+    pub fn to_le_bytes(self) -> [u8; 24] {
+        let mut bytes = [0u8; 24];
+        self.to_little_endian(&mut bytes);
+        bytes
+    }
+
+    /// Creates an integer from its memory representation as a byte array in little-endian order.
+    pub fn from_le_bytes(bytes: [u8; 24]) -> Self {
+        Self::from_little_endian(&bytes)
+    }
+
+    /// Parses a decimal string, the inverse of `Display`. Unlike `from_dec_str` the error tells
+    /// which character is invalid.
+    pub fn parse_decimal(value: &str) -> Result<Self, ParseDecimalError> {
+        if value.is_empty() {
+            return Err(ParseDecimalError::Empty);
+        }
+
+        let ten = U192::from(10u8);
+        value
+            .chars()
+            .enumerate()
+            .try_fold(U192::zero(), |result, (position, character)| {
+                let digit = character
+                    .to_digit(10)
+                    .ok_or(ParseDecimalError::InvalidDigit { position, character })?;
+                result
+                    .checked_mul(ten)
+                    .and_then(|result| result.checked_add(U192::from(digit)))
+                    .ok_or(ParseDecimalError::Overflow)
+            })
+    }
+}
+
+/// Error parsing a decimal string into a `U192`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseDecimalError {
+    /// The string has no digits.
+    Empty,
+    /// A character that isn't a decimal digit, at the given character position.
+    InvalidDigit { position: usize, character: char },
+    /// The value is larger than `U192::MAX`.
+    Overflow,
+}
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseDecimalError::Empty => write!(f, "cannot parse an empty string as a decimal integer"),
+            ParseDecimalError::InvalidDigit { position, character } => {
+                write!(f, "invalid decimal digit {:?} at position {}", character, position)
+            }
+            ParseDecimalError::Overflow => write!(f, "decimal integer is larger than U192::MAX"),
+        }
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+/// `U192` whose `FromStr` parses decimal, so it round-trips through `Display` and works with
+/// `str::parse` and string-based (de)serializers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DecimalU192(pub U192);
+
+impl FromStr for DecimalU192 {
+    type Err = ParseDecimalError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        U192::parse_decimal(value).map(DecimalU192)
+    }
+}
+
+impl fmt::Display for DecimalU192 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<U192> for DecimalU192 {
+    fn from(value: U192) -> Self {
+        DecimalU192(value)
+    }
+}
+
+impl From<DecimalU192> for U192 {
+    fn from(value: DecimalU192) -> Self {
+        value.0
+    }
+}
+
 #[macro_export]
 macro_rules! uint192 {
     ($value:expr) => {
@@ -13,6 +118,13 @@ macro_rules! uint192 {
     };
 }
 
+#[macro_export]
+macro_rules! uint256 {
+    ($value:expr) => {
+        U256::from($value)
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -20,10 +132,140 @@ mod tests {
     #[test]
     fn test_shift_for_div_by_2() {
         assert_eq!(uint192!(u128::MAX) >> 1, uint192!(u128::MAX) / (uint192!(2)));
+        assert_eq!(uint256!(u128::MAX) >> 1, uint256!(u128::MAX) / (uint256!(2)));
     }
 
     #[test]
     fn test_shift_for_mul_by_2() {
         assert_eq!(uint192!(u128::MAX) << 1, uint192!(u128::MAX) * uint192!(2));
+        assert_eq!(uint256!(u128::MAX) << 1, uint256!(u128::MAX) * uint256!(2));
+    }
+
+    #[test]
+    fn test_integer_sqrt() {
+        assert_eq!(uint192!(0).integer_sqrt(), uint192!(0));
+        assert_eq!(uint192!(1).integer_sqrt(), uint192!(1));
+        assert_eq!(uint192!(2).integer_sqrt(), uint192!(1));
+        assert_eq!(uint192!(4).integer_sqrt(), uint192!(2));
+        assert_eq!(uint192!(99).integer_sqrt(), uint192!(9));
+        assert_eq!(uint192!(100).integer_sqrt(), uint192!(10));
+        assert_eq!(
+            uint192!(u64::MAX as u128 * u64::MAX as u128).integer_sqrt(),
+            uint192!(u64::MAX)
+        );
+        assert_eq!(uint192!(u128::MAX).integer_sqrt(), uint192!(u64::MAX));
+        let root = (uint192!(1) << 96) - 1;
+        assert_eq!((root * root).integer_sqrt(), root);
+        assert_eq!(U192::MAX.integer_sqrt(), root);
+    }
+
+    #[test]
+    fn test_le_bytes() {
+        for x in [
+            U192::zero(),
+            uint192!(1),
+            uint192!(u64::MAX) + 1,
+            uint192!(u128::MAX),
+            U192::MAX,
+        ] {
+            assert_eq!(U192::from_le_bytes(x.to_le_bytes()), x);
+        }
+
+        let bytes = uint192!(0x0102).to_le_bytes();
+        assert_eq!(bytes[..3], [0x02, 0x01, 0x00]);
+        assert_eq!(uint192!(u128::MAX).to_le_bytes()[15..17], [0xff, 0x00]);
+        assert_eq!(U192::MAX.to_le_bytes(), [0xff; 24]);
+    }
+
+    #[test]
+    fn test_decimal_strings() {
+        for x in [
+            U192::zero(),
+            uint192!(7),
+            uint192!(1_000_000_000),
+            uint192!(u64::MAX),
+            uint192!(u128::MAX),
+            U192::MAX,
+        ] {
+            assert_eq!(U192::from_dec_str(&x.to_string()), Ok(x));
+        }
+
+        assert_eq!(uint192!(1_000_000_000).to_string(), "1000000000");
+        assert_eq!(
+            U192::MAX.to_string(),
+            "6277101735386680763835789423207666416102355444464034512895"
+        );
+        assert_eq!(U192::from_dec_str("12a"), Err(FromDecStrErr::InvalidCharacter));
+        assert_eq!(U192::from_dec_str("-1"), Err(FromDecStrErr::InvalidCharacter));
+        assert_eq!(
+            U192::from_dec_str("6277101735386680763835789423207666416102355444464034512896"),
+            Err(FromDecStrErr::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        for x in [
+            U192::zero(),
+            uint192!(7),
+            uint192!(1_000_000_000),
+            uint192!(u64::MAX),
+            uint192!(u128::MAX),
+            U192::MAX,
+        ] {
+            assert_eq!(U192::parse_decimal(&x.to_string()), Ok(x));
+            assert_eq!(DecimalU192(x).to_string(), x.to_string());
+            assert_eq!(DecimalU192::from_str(&DecimalU192(x).to_string()), Ok(DecimalU192(x)));
+            assert_eq!(x.to_string().parse::<DecimalU192>().map(U192::from), Ok(x));
+        }
+
+        // the derived `FromStr` reads hex, which is why the decimal entry point exists
+        assert_eq!(U192::from_str("10").unwrap(), uint192!(16));
+        assert_eq!(U192::parse_decimal("10"), Ok(uint192!(10)));
+        assert_eq!(U192::parse_decimal("007"), Ok(uint192!(7)));
+
+        assert_eq!(U192::parse_decimal(""), Err(ParseDecimalError::Empty));
+        assert_eq!(
+            U192::parse_decimal("12a"),
+            Err(ParseDecimalError::InvalidDigit {
+                position: 2,
+                character: 'a'
+            })
+        );
+        assert_eq!(
+            U192::parse_decimal("-1"),
+            Err(ParseDecimalError::InvalidDigit {
+                position: 0,
+                character: '-'
+            })
+        );
+        assert_eq!(
+            U192::parse_decimal("6277101735386680763835789423207666416102355444464034512896"),
+            Err(ParseDecimalError::Overflow)
+        );
+        assert_eq!(
+            ParseDecimalError::InvalidDigit {
+                position: 2,
+                character: 'a'
+            }
+            .to_string(),
+            "invalid decimal digit 'a' at position 2"
+        );
+    }
+
+    #[test]
+    fn test_checked_pow() {
+        let x = uint192!(1_000_000_007);
+        assert_eq!(x.checked_pow(uint192!(0)), Some(uint192!(1)));
+        assert_eq!(x.checked_pow(uint192!(1)), Some(x));
+        assert_eq!(x.checked_pow(uint192!(3)), Some(x * x * x));
+        assert_eq!(uint192!(2).checked_pow(uint192!(191)), Some(uint192!(1) << 191));
+        assert_eq!(uint192!(2).checked_pow(uint192!(192)), None);
+        assert_eq!(
+            uint192!(u64::MAX).checked_pow(uint192!(3)),
+            Some(uint192!(u64::MAX).pow(uint192!(3)))
+        );
+        assert_eq!(uint192!(u64::MAX).checked_pow(uint192!(4)), None);
+        assert_eq!(uint192!(0).checked_pow(uint192!(0)), Some(uint192!(1)));
     }
 }
@@ -1,4 +1,13 @@
-use crate::{uint192, U192};
+use crate::{uint192, uint256, U192, U256};
+
+/// Rounding direction for operations whose exact result is not representable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round towards zero.
+    Down,
+    /// Round away from zero.
+    Up,
+}
 
 /// Trait for calculating `val * num / denom` with different rounding modes and overflow
 /// protection.
@@ -21,6 +30,33 @@ pub trait CheckedMulDiv<RHS = Self> {
     /// Calculates `ceil(val * num / denom)`, i.e. the the smallest integer greater than or equal to
     /// the result of the division.
     fn checked_mul_div_up(self, num: RHS, denom: RHS) -> Option<Self::Output>;
+
+    /// Calculates `val * num / denom`, rounding the result in the given direction.
+    fn checked_mul_div(self, num: RHS, denom: RHS, rounding: Rounding) -> Option<Self::Output>
+    where
+        Self: Sized,
+    {
+        match rounding {
+            Rounding::Down => self.checked_mul_div_down(num, denom),
+            Rounding::Up => self.checked_mul_div_up(num, denom),
+        }
+    }
+}
+
+/// Saturating counterpart of [`CheckedMulDiv`], for estimates where a hard failure isn't
+/// warranted.
+///
+/// Results that do not fit saturate to the largest value the matching [`CheckedMulDiv`] returns,
+/// which is `u64::MAX` for `u64` and `u128::MAX` for `U192`, and a zero `denom` yields `0`.
+pub trait SaturatingMulDiv<RHS = Self> {
+    /// Output type for the methods of this trait.
+    type Output;
+
+    /// Calculates `floor(val * num / denom)`, saturating on overflow.
+    fn saturating_mul_div_down(self, num: RHS, denom: RHS) -> Self::Output;
+
+    /// Calculates `ceil(val * num / denom)`, saturating on overflow.
+    fn saturating_mul_div_up(self, num: RHS, denom: RHS) -> Self::Output;
 }
 
 pub trait CheckedDivCeil<RHS = Self> {
@@ -41,10 +77,36 @@ pub trait CheckedDivFloor<RHS = Self> {
     fn checked_div_down(self, denom: RHS) -> Option<Self::Output>;
 }
 
+pub trait CheckedAddMul<RHS = Self> {
+    /// Output type for the methods of this trait.
+    type Output;
+
+    /// Calculates `val + mul_a * mul_b`, returning `None` if either the product or the sum
+    /// overflows.
+    fn checked_add_mul(self, mul_a: RHS, mul_b: RHS) -> Option<Self::Output>;
+}
+
+/// Exponentiation with a plain integer exponent.
+///
+/// `U192` already has an inherent `checked_pow` taking a `U192` exponent, which method call syntax
+/// resolves to first, so this one is called as `CheckedPow::checked_pow(x, exp)`.
+pub trait CheckedPow {
+    /// Output type for the methods of this trait.
+    type Output;
+
+    /// Calculates `val^exp` by squaring, returning `None` if the result overflows.
+    fn checked_pow(self, exp: u32) -> Option<Self::Output>;
+}
+
 pub trait Upcast {
     fn as_u192(self) -> U192;
 }
 
+/// Widening to `U256`, kept apart from [`Upcast`] so implementors of the latter don't have to support it.
+pub trait UpcastU256 {
+    fn as_u256(self) -> U256;
+}
+
 pub trait Downcast {
     /// Unsafe cast to U128
     /// Bits beyond the 128th position are lost
@@ -57,12 +119,48 @@ impl Upcast for u128 {
     }
 }
 
+impl UpcastU256 for u128 {
+    fn as_u256(self) -> U256 {
+        uint256!(self)
+    }
+}
+
 impl Downcast for U192 {
     fn as_u128(self) -> u128 {
         self.0[0] as u128 + ((self.0[1] as u128) << 64)
     }
 }
 
+impl U192 {
+    /// Checked cast to U128
+    /// None if any bit beyond the 128th position is set
+    pub fn as_u128_checked(self) -> Option<u128> {
+        if self.bits() <= 128 {
+            Some(Downcast::as_u128(self))
+        } else {
+            None
+        }
+    }
+}
+
+impl Downcast for U256 {
+    fn as_u128(self) -> u128 {
+        self.0[0] as u128 + ((self.0[1] as u128) << 64)
+    }
+}
+
+impl U256 {
+    /// Checked cast to U128
+    /// None if any bit beyond the 128th position is set
+    pub fn as_u128_checked(self) -> Option<u128> {
+        if self.bits() <= 128 {
+            Some(Downcast::as_u128(self))
+        } else {
+            None
+        }
+    }
+}
+
 impl CheckedMulDiv for u64 {
     type Output = u64;
 
@@ -87,6 +185,24 @@ impl CheckedMulDiv for u64 {
     }
 }
 
+impl SaturatingMulDiv for u64 {
+    type Output = u64;
+
+    fn saturating_mul_div_down(self, num: Self, denom: Self) -> Self::Output {
+        if denom == 0 {
+            return 0;
+        }
+        self.checked_mul_div_down(num, denom).unwrap_or(u64::MAX)
+    }
+
+    fn saturating_mul_div_up(self, num: Self, denom: Self) -> Self::Output {
+        if denom == 0 {
+            return 0;
+        }
+        self.checked_mul_div_up(num, denom).unwrap_or(u64::MAX)
+    }
+}
+
 impl CheckedDivCeil for u64 {
     type Output = u64;
 
@@ -101,12 +217,20 @@ impl CheckedDivCeil for u64 {
     }
 }
 
+impl CheckedDivFloor for u64 {
+    type Output = u64;
+
+    fn checked_div_down(self, denom: Self) -> Option<Self::Output> {
+        self.checked_div(denom)
+    }
+}
+
 impl CheckedMulDiv for U192 {
     type Output = U192;
 
     fn checked_mul_div_down(self, num: Self, denom: Self) -> Option<Self::Output> {
         assert_ne!(denom, U192::default());
-        let r = (self * num) / denom;
+        let r = self.checked_mul(num)? / denom;
         if r > u128::MAX.as_u192() {
             None
         } else {
@@ -116,7 +240,9 @@ impl CheckedMulDiv for U192 {
 
     fn checked_mul_div_up(self, num: Self, denom: Self) -> Option<Self::Output> {
         assert_ne!(denom, U192::default());
-        let r = (self * num + (denom - 1)) / denom;
+        // Rounding up from the remainder, since adding `denom - 1` first can overflow
+        let (q, rem) = self.checked_mul(num)?.div_mod(denom);
+        let r = if rem.is_zero() { q } else { q + 1 };
         if r > u128::MAX.as_u192() {
             None
         } else {
@@ -125,12 +251,31 @@ impl CheckedMulDiv for U192 {
     }
 }
 
+impl SaturatingMulDiv for U192 {
+    type Output = U192;
+
+    fn saturating_mul_div_down(self, num: Self, denom: Self) -> Self::Output {
+        if denom.is_zero() {
+            return U192::zero();
+        }
+        self.checked_mul_div_down(num, denom).unwrap_or(u128::MAX.as_u192())
+    }
+
+    fn saturating_mul_div_up(self, num: Self, denom: Self) -> Self::Output {
+        if denom.is_zero() {
+            return U192::zero();
+        }
+        self.checked_mul_div_up(num, denom).unwrap_or(u128::MAX.as_u192())
+    }
+}
+
 impl CheckedDivCeil for U192 {
     type Output = U192;
 
     fn checked_div_up(self, denom: Self) -> Option<Self::Output> {
         assert_ne!(denom, U192::default());
-        let r = (self + (denom - 1)) / denom;
+        let (q, rem) = self.div_mod(denom);
+        let r = if rem.is_zero() { q } else { q + 1 };
         if r > u128::MAX.as_u192() {
             None
         } else {
@@ -153,11 +298,114 @@ impl CheckedDivFloor for U192 {
     }
 }
 
+impl CheckedAddMul for U192 {
+    type Output = U192;
+
+    fn checked_add_mul(self, mul_a: Self, mul_b: Self) -> Option<Self::Output> {
+        let (product, mul_overflow) = mul_a.overflowing_mul(mul_b);
+        let (r, add_overflow) = self.overflowing_add(product);
+        if mul_overflow || add_overflow {
+            None
+        } else {
+            Some(r)
+        }
+    }
+}
+
+impl CheckedMulDiv for U256 {
+    type Output = U256;
+
+    fn checked_mul_div_down(self, num: Self, denom: Self) -> Option<Self::Output> {
+        assert_ne!(denom, U256::default());
+        let r = self.checked_mul(num)? / denom;
+        if r > u128::MAX.as_u256() {
+            None
+        } else {
+            Some(r)
+        }
+    }
+
+    fn checked_mul_div_up(self, num: Self, denom: Self) -> Option<Self::Output> {
+        assert_ne!(denom, U256::default());
+        // Rounding up from the remainder, since adding `denom - 1` first can overflow
+        let (q, rem) = self.checked_mul(num)?.div_mod(denom);
+        let r = if rem.is_zero() { q } else { q + 1 };
+        if r > u128::MAX.as_u256() {
+            None
+        } else {
+            Some(r)
+        }
+    }
+}
+
+impl CheckedDivCeil for U256 {
+    type Output = U256;
+
+    fn checked_div_up(self, denom: Self) -> Option<Self::Output> {
+        assert_ne!(denom, U256::default());
+        let (q, rem) = self.div_mod(denom);
+        let r = if rem.is_zero() { q } else { q + 1 };
+        if r > u128::MAX.as_u256() {
+            None
+        } else {
+            Some(r)
+        }
+    }
+}
+
+impl CheckedDivFloor for U256 {
+    type Output = U256;
+
+    fn checked_div_down(self, denom: Self) -> Option<Self::Output> {
+        assert_ne!(denom, U256::default());
+        let r = self / denom;
+        if r > u128::MAX.as_u256() {
+            None
+        } else {
+            Some(r)
+        }
+    }
+}
+
+impl CheckedAddMul for U256 {
+    type Output = U256;
+
+    fn checked_add_mul(self, mul_a: Self, mul_b: Self) -> Option<Self::Output> {
+        let (product, mul_overflow) = mul_a.overflowing_mul(mul_b);
+        let (r, add_overflow) = self.overflowing_add(product);
+        if mul_overflow || add_overflow {
+            None
+        } else {
+            Some(r)
+        }
+    }
+}
+
+impl CheckedPow for U192 {
+    type Output = U192;
+
+    fn checked_pow(self, mut exp: u32) -> Option<Self::Output> {
+        let mut base = self;
+        let mut r = U192::one();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                r = r.checked_mul(base)?;
+            }
+            exp >>= 1;
+            // the last square is never used, so it must not fail the whole power
+            if exp > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Some(r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::uint192;
+    use crate::{uint192, uint256};
 
     #[test]
     fn test_casting_overflow() {
@@ -165,5 +413,181 @@ mod tests {
         assert_eq!(uint192!(u64::MAX).as_u64(), u64::MAX);
         assert_eq!(uint192!(u128::MAX).as_u128(), u128::MAX);
         assert_eq!(uint192!(u128::MAX), u128::MAX.as_u192());
+
+        assert_eq!(uint256!(0), U256::zero());
+        assert_eq!(uint256!(u64::MAX).as_u64(), u64::MAX);
+        assert_eq!(uint256!(u128::MAX).as_u128(), u128::MAX);
+        assert_eq!(uint256!(u128::MAX), u128::MAX.as_u256());
+    }
+
+    #[test]
+    fn test_as_u128_checked() {
+        assert_eq!(U192::zero().as_u128_checked(), Some(0));
+        assert_eq!(uint192!(u64::MAX).as_u128_checked(), Some(u64::MAX as u128));
+        assert_eq!(uint192!(u128::MAX).as_u128_checked(), Some(u128::MAX));
+        assert_eq!((uint192!(u128::MAX) + 1).as_u128_checked(), None);
+        assert_eq!(U192::MAX.as_u128_checked(), None);
+
+        assert_eq!(U256::zero().as_u128_checked(), Some(0));
+        assert_eq!(uint256!(u128::MAX).as_u128_checked(), Some(u128::MAX));
+        assert_eq!((uint256!(u128::MAX) + 1).as_u128_checked(), None);
+        assert_eq!((U256::one() << 192).as_u128_checked(), None);
+        assert_eq!(U256::MAX.as_u128_checked(), None);
+    }
+
+    #[test]
+    fn test_checked_pow() {
+        let x = uint192!(1_000_000_007);
+        assert_eq!(CheckedPow::checked_pow(x, 0), Some(U192::one()));
+        assert_eq!(CheckedPow::checked_pow(x, 1), Some(x));
+        assert_eq!(CheckedPow::checked_pow(x, 3), Some(x * x * x));
+        assert_eq!(CheckedPow::checked_pow(U192::zero(), 0), Some(U192::one()));
+        assert_eq!(CheckedPow::checked_pow(U192::MAX, 1), Some(U192::MAX));
+        // 2^191 is the largest power of two that fits
+        assert_eq!(CheckedPow::checked_pow(uint192!(2), 191), Some(U192::one() << 191));
+        assert_eq!(CheckedPow::checked_pow(uint192!(2), 192), None);
+        assert_eq!(
+            CheckedPow::checked_pow(uint192!(u64::MAX), 3),
+            Some(uint192!(u64::MAX) * uint192!(u64::MAX) * uint192!(u64::MAX))
+        );
+        assert_eq!(CheckedPow::checked_pow(uint192!(u64::MAX), 4), None);
+        assert_eq!(CheckedPow::checked_pow(U192::one(), u32::MAX), Some(U192::one()));
+    }
+
+    #[test]
+    fn test_checked_add_mul() {
+        assert_eq!(uint192!(1).checked_add_mul(uint192!(2), uint192!(3)), Some(uint192!(7)));
+        assert_eq!(
+            U192::zero().checked_add_mul(U192::zero(), U192::MAX),
+            Some(U192::zero())
+        );
+        assert_eq!(uint192!(5).checked_add_mul(U192::MAX, U192::zero()), Some(uint192!(5)));
+        assert_eq!(U192::zero().checked_add_mul(U192::MAX, U192::one()), Some(U192::MAX));
+        assert_eq!(
+            (U192::MAX - 6).checked_add_mul(uint192!(2), uint192!(3)),
+            Some(U192::MAX)
+        );
+        assert_eq!((U192::MAX - 5).checked_add_mul(uint192!(2), uint192!(3)), None);
+        // the product overflows on its own, the sum never gets the chance
+        assert_eq!(U192::zero().checked_add_mul(U192::one() << 96, U192::one() << 96), None);
+        assert_eq!(U192::zero().checked_add_mul(U192::MAX, uint192!(2)), None);
+
+        assert_eq!(uint256!(1).checked_add_mul(uint256!(2), uint256!(3)), Some(uint256!(7)));
+        assert_eq!(
+            U256::zero().checked_add_mul(U256::one() << 96, U256::one() << 96),
+            Some(U256::one() << 192)
+        );
+        assert_eq!((U256::MAX - 5).checked_add_mul(uint256!(2), uint256!(3)), None);
+        assert_eq!(
+            U256::zero().checked_add_mul(U256::one() << 128, U256::one() << 128),
+            None
+        );
+    }
+
+    #[test]
+    fn test_u64_div() {
+        assert_eq!(10u64.checked_div_down(3), Some(3));
+        assert_eq!(10u64.checked_div_up(3), Some(4));
+        assert_eq!(9u64.checked_div_down(3), Some(3));
+        assert_eq!(9u64.checked_div_up(3), Some(3));
+        assert_eq!(u64::MAX.checked_div_down(1), Some(u64::MAX));
+        assert_eq!(u64::MAX.checked_div_down(u64::MAX), Some(1));
+        assert_eq!(0u64.checked_div_down(7), Some(0));
+        assert_eq!(7u64.checked_div_down(0), None);
+    }
+
+    #[test]
+    fn test_checked_mul_div_rounding() {
+        assert_eq!(10u64.checked_mul_div(10, 3, Rounding::Down), Some(33));
+        assert_eq!(10u64.checked_mul_div(10, 3, Rounding::Up), Some(34));
+        assert_eq!(9u64.checked_mul_div(10, 3, Rounding::Up), Some(30));
+        assert_eq!(u64::MAX.checked_mul_div(2, 1, Rounding::Down), None);
+
+        let (a, b, c) = (uint192!(10), uint192!(10), uint192!(3));
+        assert_eq!(a.checked_mul_div(b, c, Rounding::Down), a.checked_mul_div_down(b, c));
+        assert_eq!(a.checked_mul_div(b, c, Rounding::Up), a.checked_mul_div_up(b, c));
+
+        let (a, b, c) = (uint256!(10), uint256!(10), uint256!(3));
+        assert_eq!(a.checked_mul_div(b, c, Rounding::Down), a.checked_mul_div_down(b, c));
+        assert_eq!(a.checked_mul_div(b, c, Rounding::Up), a.checked_mul_div_up(b, c));
+    }
+
+    #[test]
+    fn test_saturating_mul_div() {
+        assert_eq!(10u64.saturating_mul_div_down(10, 3), 33);
+        assert_eq!(10u64.saturating_mul_div_up(10, 3), 34);
+        assert_eq!(u64::MAX.saturating_mul_div_down(u64::MAX, u64::MAX), u64::MAX);
+        assert_eq!(u64::MAX.saturating_mul_div_up(2, 2), u64::MAX);
+        assert_eq!(u64::MAX.saturating_mul_div_down(2, 1), u64::MAX);
+        // The down result is exactly `u64::MAX`, while rounding up crosses the boundary
+        let x = 16_769_767_339_735_956_014u64;
+        assert_eq!(x.checked_mul_div_down(11, 10), Some(u64::MAX));
+        assert_eq!(x.checked_mul_div_up(11, 10), None);
+        assert_eq!(x.saturating_mul_div_down(11, 10), u64::MAX);
+        assert_eq!(x.saturating_mul_div_up(11, 10), u64::MAX);
+        assert_eq!((x - 1).saturating_mul_div_down(11, 10), u64::MAX - 1);
+        assert_eq!(7u64.saturating_mul_div_down(3, 0), 0);
+        assert_eq!(7u64.saturating_mul_div_up(3, 0), 0);
+
+        let max = u128::MAX.as_u192();
+        assert_eq!(max.saturating_mul_div_down(uint192!(3), uint192!(3)), max);
+        assert_eq!(max.saturating_mul_div_up(uint192!(3), uint192!(3)), max);
+        // results past the checked range saturate to its cap, not to `U192::MAX`
+        assert_eq!(max.checked_mul_div_down(uint192!(2), uint192!(1)), None);
+        assert_eq!(max.saturating_mul_div_down(uint192!(2), uint192!(1)), max);
+        assert_eq!(max.saturating_mul_div_up(max, uint192!(1)), max);
+        assert_eq!(U192::MAX.saturating_mul_div_down(U192::MAX, uint192!(1)), max);
+        assert_eq!((max - 1).saturating_mul_div_down(uint192!(1), uint192!(1)), max - 1);
+        assert_eq!(
+            uint192!(7).saturating_mul_div_down(uint192!(3), U192::zero()),
+            U192::zero()
+        );
+        assert_eq!(
+            uint192!(7).saturating_mul_div_up(uint192!(3), U192::zero()),
+            U192::zero()
+        );
+    }
+
+    #[test]
+    fn test_mul_div_up_near_max() {
+        // (2^96 - 1) * (2^96 + 1) == U192::MAX, so adding `denom - 1` before dividing overflows
+        let a = (uint192!(1) << 96) - 1;
+        let b = (uint192!(1) << 96) + 1;
+        assert_eq!(a * b, U192::MAX);
+        assert_eq!(a.checked_mul_div_up(b, b), Some(a));
+        assert_eq!(a.checked_mul_div_up(b, b - 1), Some(a + 1));
+        assert_eq!(a.checked_mul_div_down(b, b - 1), Some(a));
+        assert_eq!(U192::MAX.checked_div_up(b), Some(a));
+        assert_eq!(U192::MAX.checked_div_up(b - 1), Some(a + 1));
+        assert_eq!(a.checked_mul_div_up(b + 1, b), None);
+
+        let a = (uint256!(1) << 128) - 1;
+        let b = (uint256!(1) << 128) + 1;
+        assert_eq!(a * b, U256::MAX);
+        assert_eq!(a.checked_mul_div_up(b, b), Some(a));
+        assert_eq!(U256::MAX.checked_div_up(b), Some(a));
+    }
+
+    #[test]
+    fn test_u256_mul_div() {
+        let max = u128::MAX.as_u256();
+        assert_eq!(max.checked_mul_div_down(max, max), Some(max));
+        assert_eq!(max.checked_mul_div_up(max, max), Some(max));
+        assert_eq!(max.checked_mul_div_down(max, max - 1), None);
+
+        let a = uint256!(u64::MAX) * uint256!(u64::MAX) * uint256!(u64::MAX);
+        assert_eq!(a.checked_mul_div_down(uint256!(3), uint256!(u64::MAX)), None);
+        assert_eq!(a.checked_mul_div_down(uint256!(2), a), Some(uint256!(2)));
+        assert_eq!(
+            uint256!(10).checked_mul_div_down(uint256!(10), uint256!(3)),
+            Some(uint256!(33))
+        );
+        assert_eq!(
+            uint256!(10).checked_mul_div_up(uint256!(10), uint256!(3)),
+            Some(uint256!(34))
+        );
+        assert_eq!(uint256!(100).checked_div_up(uint256!(3)), Some(uint256!(34)));
+        assert_eq!(uint256!(100).checked_div_down(uint256!(3)), Some(uint256!(33)));
+        assert_eq!(a.checked_div_down(uint256!(1)), None);
     }
 }
@@ -0,0 +1,268 @@
+use crate::safe_math::Rounding;
+use crate::U192;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+
+/// Signed 192-bit integer, stored as a sign and a `U192` magnitude.
+///
+/// The range is symmetric, `-(2^192 - 1)..=2^192 - 1`, and zero is never negative, so values can
+/// be compared with `==` directly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct I192 {
+    negative: bool,
+    magnitude: U192,
+}
+
+impl I192 {
+    pub const MAX: I192 = I192 {
+        negative: false,
+        magnitude: U192::MAX,
+    };
+
+    pub const MIN: I192 = I192 {
+        negative: true,
+        magnitude: U192::MAX,
+    };
+
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    /// Creates an integer from its sign and magnitude, a negative zero is normalized to zero.
+    pub fn from_parts(negative: bool, magnitude: U192) -> Self {
+        Self {
+            negative: negative && !magnitude.is_zero(),
+            magnitude,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_positive(&self) -> bool {
+        !self.negative && !self.magnitude.is_zero()
+    }
+
+    /// Absolute value of the integer.
+    pub fn magnitude(&self) -> U192 {
+        self.magnitude
+    }
+
+    /// Returns the value as an unsigned integer, or `None` if it is negative.
+    pub fn to_unsigned(self) -> Option<U192> {
+        if self.negative {
+            None
+        } else {
+            Some(self.magnitude)
+        }
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        if self.negative == rhs.negative {
+            return Some(Self::from_parts(
+                self.negative,
+                self.magnitude.checked_add(rhs.magnitude)?,
+            ));
+        }
+        // opposite signs never overflow, the larger magnitude decides the sign
+        if self.magnitude >= rhs.magnitude {
+            Some(Self::from_parts(self.negative, self.magnitude - rhs.magnitude))
+        } else {
+            Some(Self::from_parts(rhs.negative, rhs.magnitude - self.magnitude))
+        }
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_add(-rhs)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        Some(Self::from_parts(
+            self.negative != rhs.negative,
+            self.magnitude.checked_mul(rhs.magnitude)?,
+        ))
+    }
+
+    /// Calculates `val * num / denom`, rounding the magnitude of the result in the given direction,
+    /// i.e. [`Rounding::Down`] rounds towards zero and [`Rounding::Up`] away from zero.
+    ///
+    /// Returns `None` if `denom` is zero, or if the product or the result does not fit.
+    pub fn checked_mul_div(self, num: Self, denom: Self, rounding: Rounding) -> Option<Self> {
+        if denom.is_zero() {
+            return None;
+        }
+        let (q, rem) = self.magnitude.checked_mul(num.magnitude)?.div_mod(denom.magnitude);
+        let magnitude = match rounding {
+            Rounding::Up if !rem.is_zero() => q.checked_add(U192::one())?,
+            _ => q,
+        };
+        Some(Self::from_parts(
+            self.negative ^ num.negative ^ denom.negative,
+            magnitude,
+        ))
+    }
+}
+
+impl From<U192> for I192 {
+    fn from(value: U192) -> Self {
+        Self::from_parts(false, value)
+    }
+}
+
+impl From<u128> for I192 {
+    fn from(value: u128) -> Self {
+        Self::from_parts(false, U192::from(value))
+    }
+}
+
+impl From<i128> for I192 {
+    fn from(value: i128) -> Self {
+        Self::from_parts(value < 0, U192::from(value.unsigned_abs()))
+    }
+}
+
+// negation can't overflow since the range is symmetric
+impl Neg for I192 {
+    type Output = I192;
+
+    fn neg(self) -> Self::Output {
+        Self::from_parts(!self.negative, self.magnitude)
+    }
+}
+
+impl Ord for I192 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for I192 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for I192 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uint192;
+
+    fn int(value: i128) -> I192 {
+        I192::from(value)
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(I192::from_parts(true, U192::zero()), I192::zero());
+        assert!(!I192::from_parts(true, U192::zero()).is_negative());
+        assert!(int(-1).is_negative());
+        assert!(int(1).is_positive());
+        assert!(!int(0).is_positive());
+        assert_eq!(int(-7).magnitude(), uint192!(7));
+        assert_eq!(int(-7).to_unsigned(), None);
+        assert_eq!(int(7).to_unsigned(), Some(uint192!(7)));
+        assert_eq!(-int(0), int(0));
+        assert!(!(-int(0)).is_negative());
+        assert_eq!(-int(5), int(-5));
+        assert_eq!(-I192::MIN, I192::MAX);
+        assert_eq!(int(-5).to_string(), "-5");
+        assert_eq!(int(5).to_string(), "5");
+
+        assert!(int(-2) < int(-1));
+        assert!(int(-1) < int(0));
+        assert!(int(0) < int(1));
+        assert!(I192::MIN < int(i128::MIN));
+        assert!(I192::MAX > int(i128::MAX));
+    }
+
+    #[test]
+    fn test_checked_add_sub() {
+        // crossing zero in both directions
+        assert_eq!(int(3).checked_sub(int(5)), Some(int(-2)));
+        assert_eq!(int(-3).checked_add(int(5)), Some(int(2)));
+        assert_eq!(int(-3).checked_add(int(3)), Some(int(0)));
+        assert!(!int(-3).checked_add(int(3)).unwrap().is_negative());
+        assert_eq!(int(3).checked_sub(int(3)), Some(int(0)));
+        assert_eq!(int(-3).checked_sub(int(-5)), Some(int(2)));
+        assert_eq!(int(-3).checked_sub(int(2)), Some(int(-5)));
+        assert_eq!(int(3).checked_add(int(-5)), Some(int(-2)));
+
+        assert_eq!(I192::MAX.checked_add(int(1)), None);
+        assert_eq!(I192::MIN.checked_sub(int(1)), None);
+        assert_eq!(I192::MAX.checked_sub(int(-1)), None);
+        assert_eq!(I192::MAX.checked_add(I192::MIN), Some(int(0)));
+        assert_eq!(I192::MIN.checked_add(int(1)).unwrap().magnitude(), U192::MAX - 1);
+
+        for (a, b) in [(i64::MAX as i128, -17), (-1_000_000_007, 999), (0, -1), (-42, 0)] {
+            assert_eq!(int(a).checked_add(int(b)), Some(int(a + b)));
+            assert_eq!(int(a).checked_sub(int(b)), Some(int(a - b)));
+        }
+    }
+
+    #[test]
+    fn test_checked_mul() {
+        assert_eq!(int(-3).checked_mul(int(4)), Some(int(-12)));
+        assert_eq!(int(-3).checked_mul(int(-4)), Some(int(12)));
+        assert_eq!(int(3).checked_mul(int(-4)), Some(int(-12)));
+        assert_eq!(int(-3).checked_mul(int(0)), Some(int(0)));
+        assert!(!int(-3).checked_mul(int(0)).unwrap().is_negative());
+        assert_eq!(I192::MIN.checked_mul(int(-1)), Some(I192::MAX));
+        assert_eq!(I192::MIN.checked_mul(int(2)), None);
+    }
+
+    #[test]
+    fn test_checked_mul_div() {
+        assert_eq!(int(7).checked_mul_div(int(1), int(2), Rounding::Down), Some(int(3)));
+        assert_eq!(int(7).checked_mul_div(int(1), int(2), Rounding::Up), Some(int(4)));
+        // rounding applies to the magnitude, so down is towards zero for negative results
+        assert_eq!(int(-7).checked_mul_div(int(1), int(2), Rounding::Down), Some(int(-3)));
+        assert_eq!(int(-7).checked_mul_div(int(1), int(2), Rounding::Up), Some(int(-4)));
+        assert_eq!(int(7).checked_mul_div(int(-1), int(2), Rounding::Down), Some(int(-3)));
+        assert_eq!(int(7).checked_mul_div(int(1), int(-2), Rounding::Up), Some(int(-4)));
+        assert_eq!(int(-7).checked_mul_div(int(-1), int(-2), Rounding::Down), Some(int(-3)));
+        assert_eq!(int(-7).checked_mul_div(int(-1), int(2), Rounding::Down), Some(int(3)));
+        assert_eq!(int(-6).checked_mul_div(int(1), int(2), Rounding::Up), Some(int(-3)));
+
+        // a result rounded down to zero is not negative
+        let zero = int(-1).checked_mul_div(int(1), int(2), Rounding::Down).unwrap();
+        assert_eq!(zero, int(0));
+        assert!(!zero.is_negative());
+
+        assert_eq!(int(1).checked_mul_div(int(1), int(0), Rounding::Down), None);
+        assert_eq!(I192::MAX.checked_mul_div(int(2), int(2), Rounding::Down), None);
+        assert_eq!(
+            I192::MIN.checked_mul_div(int(1), int(-1), Rounding::Up),
+            Some(I192::MAX)
+        );
+        // the product may exceed 128 bits as long as it fits into 192
+        let big = int(1 << 90);
+        assert_eq!(
+            big.checked_mul_div(big, int(-(1 << 90)), Rounding::Down),
+            Some(int(-(1 << 90)))
+        );
+        assert_eq!(
+            int(i128::MAX).checked_mul_div(int(i128::MAX), int(1), Rounding::Down),
+            None
+        );
+    }
+}
@@ -10,13 +10,16 @@
 //! assert_eq!(U34F30::from_num(8.0), x.powf(U34F30::from_num(1.5)));
 //! ```
 
-use std::cmp::{Ord, Ordering};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+use core::cmp::{Ord, Ordering};
+
+use fixed::consts;
 use fixed::traits::Fixed;
-use fixed::types::extra::LeEqU64;
-use fixed::FixedU64;
-use num_traits::{One, PrimInt, Zero};
-use typenum::{Bit, IsLessOrEqual, LeEq, True, Unsigned, U63};
+use fixed::types::extra::{LeEqU128, LeEqU64};
+use fixed::{FixedU128, FixedU64};
+use num_traits::{CheckedMul, One, PrimInt, Zero};
+use typenum::{Bit, IsLessOrEqual, LeEq, True, Unsigned, U127, U63};
 
 /// Extension trait providing fixed-point exponentiation for fixed-point numbers.
 pub trait FixedPowF: Fixed {
@@ -32,9 +35,136 @@ pub trait FixedPowF: Fixed {
     /// assert_eq!(U34F30::from_num(8.0), x.powf(U34F30::from_num(1.5)));
     /// ```
     fn powf(self, n: Self) -> Self;
+
+    /// Raises a number to a fixed-point power, rounding so that the result is less than or equal
+    /// to the exact value. Returns `None` if the result overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedPowF;
+    ///
+    /// let x = U34F30::from_num(2.0);
+    /// assert!(x.powf_down(U34F30::from_num(0.5)).unwrap() <= U34F30::SQRT_2);
+    /// ```
+    fn powf_down(self, n: Self) -> Option<Self>;
+
+    /// Raises a number to a fixed-point power, rounding so that the result is greater than or
+    /// equal to the exact value. Returns `None` if the result overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedPowF;
+    ///
+    /// let x = U34F30::from_num(2.0);
+    /// assert!(x.powf_up(U34F30::from_num(0.5)).unwrap() >= U34F30::SQRT_2);
+    /// ```
+    fn powf_up(self, n: Self) -> Option<Self>;
+
+    /// Raises a number to an integer power by repeated squaring, which is exact for exponents
+    /// whose intermediate results are representable. Negative powers raise the reciprocal.
+    /// Returns `None` if the result overflows or `self` is zero with a negative power.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedPowF;
+    ///
+    /// let x = U34F30::from_num(2.0);
+    /// assert_eq!(Some(U34F30::from_num(1024.0)), x.powi(10));
+    /// assert_eq!(Some(U34F30::from_num(0.25)), x.powi(-2));
+    /// ```
+    fn powi(self, n: i32) -> Option<Self>;
+
+    /// Computes the `n`-th root, i.e. the largest number whose `n`-th power does not exceed
+    /// `self`. The powers are taken with truncating multiplication, so roots that aren't exact
+    /// may come out a unit of the last fractional bit high. Returns `None` if `n` is zero or
+    /// larger than `i32::MAX`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedPowF;
+    ///
+    /// let x = U34F30::from_num(27.0);
+    /// assert_eq!(Some(U34F30::from_num(3.0)), x.nth_root(3));
+    /// ```
+    fn nth_root(self, n: u32) -> Option<Self>;
+}
+
+/// Extension trait providing the natural exponential and logarithm for fixed-point numbers.
+pub trait FixedExp: Fixed {
+    /// Raises `e` to a fixed-point power, returning `None` if the result overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedExp;
+    ///
+    /// let x = U34F30::from_num(0.0);
+    /// assert_eq!(Some(U34F30::from_num(1.0)), x.exp());
+    /// ```
+    fn exp(self) -> Option<Self>;
+
+    /// Computes the natural logarithm, returning `None` if the input is not positive or the result
+    /// is not representable (e.g. inputs below one for unsigned types).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedExp;
+    ///
+    /// let x = U34F30::from_num(1.0);
+    /// assert_eq!(Some(U34F30::from_num(0.0)), x.ln());
+    /// ```
+    fn ln(self) -> Option<Self>;
+
+    /// Computes the logarithm in an arbitrary base as `ln(self) / ln(base)`, returning `None` if
+    /// either logarithm is not representable or `base` is one.
+    ///
+    /// Each logarithm is accurate to a few units of the last fractional bit; dividing by
+    /// `ln(base)` scales that error by `1 / ln(base)`, so bases close to one are the least accurate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::types::U34F30;
+    /// use fixed_exp::FixedExp;
+    ///
+    /// let x = U34F30::from_num(8.0);
+    /// let log = x.log_base(U34F30::from_num(2.0)).unwrap();
+    /// assert!(log.dist(U34F30::from_num(3.0)) < U34F30::from_num(0.0001));
+    /// ```
+    fn log_base(self, base: Self) -> Option<Self>;
 }
 
-fn powi<T: Fixed>(mut x: T, mut n: i32) -> T {
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Rounding {
+    /// Truncates every intermediate result, as `powf` always did.
+    Truncate,
+    /// Rounds towards zero so that the result never exceeds the exact value.
+    Down,
+    /// Rounds towards positive infinity so that the result is never below the exact value.
+    Up,
+}
+
+fn mul<T: Fixed>(a: T, b: T, rounding: Rounding) -> Option<T> {
+    let product = a.checked_mul(b)?;
+
+    match rounding {
+        Rounding::Truncate | Rounding::Down => Some(product),
+        Rounding::Up => product.checked_add(T::DELTA),
+    }
+}
+
+fn powi<T: Fixed>(mut x: T, mut n: i32, rounding: Rounding) -> Option<T> {
     // n cannot be zero
 
     let mut acc = x;
@@ -42,13 +172,48 @@ fn powi<T: Fixed>(mut x: T, mut n: i32) -> T {
 
     while n > 0 {
         if n & 1 == 1 {
-            acc *= x;
+            acc = mul(acc, x, rounding)?;
         }
-        x *= x;
         n >>= 1;
+        if n > 0 {
+            x = mul(x, x, rounding)?;
+        }
     }
 
-    acc
+    Some(acc)
+}
+
+fn nth_root<T>(x: T, n: u32) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    if n == 0 {
+        return None;
+    }
+    if n == 1 || x.is_zero() || x.is_one() {
+        return Some(x);
+    }
+    let n = i32::try_from(n).ok()?;
+
+    // the truncated powers of a small root lose most of their precision, so scale x up by
+    // 2^(shift * n) as far as it goes and scale the root back down by 2^shift afterwards
+    let shift = x.to_bits().leading_zeros() / n as u32;
+    let x = x << (shift * n as u32);
+
+    // set the bits of the root from the top down, keeping each one as long as the power of the
+    // root so far stays at or below x
+    let mut root = T::Bits::zero();
+    let mut bit = T::Bits::one() << (T::NUM_BITS - 1) as usize;
+    while !bit.is_zero() {
+        let candidate = root | bit;
+        if powi(T::from_bits(candidate), n, Rounding::Truncate).is_some_and(|power| power <= x) {
+            root = candidate;
+        }
+        bit = bit >> 1;
+    }
+
+    Some(T::from_bits(root) >> shift)
 }
 
 fn sqrt<T>(x: T) -> T
@@ -60,41 +225,122 @@ where
         return x;
     }
 
-    let mut pow2 = T::one();
-    let mut result;
-
-    if x < T::one() {
-        while x <= pow2 * pow2 {
-            pow2 >>= 1;
-        }
-
-        result = pow2;
+    // the result lies in [lo, 2 * lo), where lo is the largest power of two whose square is
+    // less than or equal to x (strictly less than x if x < 1)
+    let bits = x.to_bits();
+    let int_bits = (T::NUM_BITS - bits.leading_zeros()) as i32 - 1 - T::FRAC_NBITS as i32;
+    let lo = if int_bits >= 0 {
+        T::one() << (int_bits / 2) as u32
+    } else if bits.count_ones() == 1 {
+        T::one() >> (-int_bits / 2 + 1) as u32
     } else {
-        // x >= T::one()
-        while pow2 * pow2 <= x {
-            pow2 <<= 1;
+        T::one() >> ((-int_bits + 1) / 2) as u32
+    };
+    let hi = lo + (lo - T::DELTA);
+
+    // Newton-Raphson from above
+    let mut result = hi;
+    loop {
+        let next_result = (result + x / result) >> 1;
+        if next_result >= result {
+            break;
         }
+        result = next_result;
+    }
+
+    // settle on the largest result in [lo, hi] whose square does not exceed x
+    while result * result > x {
+        result -= T::DELTA;
+    }
+    while result < hi && (result + T::DELTA) * (result + T::DELTA) <= x {
+        result += T::DELTA;
+    }
 
-        result = pow2 >> 1;
+    result
+}
+
+fn sqrt_rounded<T>(x: T, rounding: Rounding) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    if x.is_zero() || x.is_one() {
+        return Some(x);
     }
 
-    for _ in 0..T::NUM_BITS {
-        pow2 >>= 1;
-        let next_result = result + pow2;
-        if next_result * next_result <= x {
-            result = next_result;
+    // sqrt returns the largest value whose truncated square does not exceed x, which may be just
+    // above the exact root, while the next representable value is always at or above it
+    let mut result = sqrt(x);
+
+    match rounding {
+        Rounding::Truncate => Some(result),
+        Rounding::Down => {
+            while x / result < result {
+                result -= T::DELTA;
+            }
+            Some(result)
         }
+        Rounding::Up => result.checked_add(T::DELTA),
     }
+}
 
-    result
+/// First-order approximation of `x^n` for `0 < n < 1` and `x` close to one, returning `None` when
+/// it would not be at least as accurate as the `sqrt` loop.
+fn powf_01_near_one<T>(x: T, n: T, rounding: Rounding) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    // with a single bit set the sqrt loop does no multiplication and is already exact
+    if n.to_bits().count_ones() < 2 || T::FRAC_NBITS < 2 {
+        return None;
+    }
+
+    // x^n = 1 + n * (x - 1) + n * (n - 1) / 2 * (x - 1)^2 + ..., and once |x - 1| is at most
+    // 2^(-FRAC / 2) everything past the first-order term lies in (-1/4, 0] units
+    let above_one = x >= T::one();
+    let d = if above_one { x - T::one() } else { T::one() - x };
+    if d > T::one() >> T::FRAC_NBITS.div_ceil(2) {
+        return None;
+    }
+
+    // n * d is exactly (q + rem / 2^FRAC) units
+    let frac_nbits = T::FRAC_NBITS as usize;
+    let product = n.to_bits().checked_mul(&d.to_bits())?;
+    let q = T::from_bits(product >> frac_nbits);
+    let rem = product & ((T::Bits::one() << frac_nbits) - T::Bits::one());
+    let quarter = T::Bits::one() << (frac_nbits - 2);
+
+    let result = if above_one {
+        match rounding {
+            Rounding::Truncate => T::one() + q,
+            Rounding::Down if rem < quarter => T::one() + q - T::DELTA,
+            Rounding::Down => T::one() + q,
+            Rounding::Up if rem.is_zero() => T::one() + q,
+            Rounding::Up => T::one() + q + T::DELTA,
+        }
+    } else {
+        match rounding {
+            Rounding::Truncate | Rounding::Up => T::one() - q,
+            Rounding::Down if rem <= quarter + quarter + quarter => T::one() - q - T::DELTA,
+            Rounding::Down => T::one() - q - T::DELTA - T::DELTA,
+        }
+    };
+
+    Some(result)
 }
 
-fn powf_01<T>(mut x: T, n: T) -> T
+fn powf_01<T>(mut x: T, n: T, rounding: Rounding) -> Option<T>
 where
     T: Fixed + Helper,
-    T::Bits: PrimInt + std::fmt::Debug,
+    T::Bits: PrimInt,
 {
     // n cannot be zero
+
+    if let Some(result) = powf_01_near_one(x, n, rounding) {
+        return Some(result);
+    }
+
     let mut n = n.to_bits();
 
     let top = T::Bits::one() << ((T::Frac::U32 - 1) as usize);
@@ -102,26 +348,30 @@ where
     let mut acc = None;
 
     while !n.is_zero() {
-        x = sqrt(x);
+        x = sqrt_rounded(x, rounding)?;
         if !(n & top).is_zero() {
             acc = match acc {
-                Some(acc) => Some(acc * x),
+                Some(acc) => Some(mul(acc, x, rounding)?),
                 None => Some(x),
             };
         }
         n = (n << 1) & mask;
     }
 
-    acc.unwrap()
+    acc
 }
 
-fn powf<T>(x: T, n: T) -> T
+fn powf<T>(x: T, n: T, rounding: Rounding) -> Option<T>
 where
     T: Fixed + Helper,
-    T::Bits: PrimInt + std::fmt::Debug,
+    T::Bits: PrimInt,
 {
     if x.is_zero() {
-        return T::ZERO;
+        return Some(T::ZERO);
+    }
+    // 1^n is exactly 1, no need to go through the sqrt loop in powf_01
+    if x.is_one() {
+        return Some(T::one());
     }
 
     let int = n.int();
@@ -129,19 +379,100 @@ where
 
     if int.is_zero() {
         if frac.is_zero() {
-            T::one()
+            Some(T::one())
         } else {
-            powf_01(x, frac)
+            powf_01(x, frac, rounding)
         }
     } else {
-        let powi = powi(x, int.to_num());
+        let powi = powi(x, int.checked_to_num()?, rounding)?;
 
         if frac.is_zero() {
-            powi
+            Some(powi)
         } else {
-            powi * powf_01(x, frac)
+            mul(powi, powf_01(x, frac, rounding)?, rounding)
+        }
+    }
+}
+
+fn exp<T>(x: T) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    if x.is_zero() {
+        return Some(T::one());
+    }
+
+    // range reduction: x = k * ln(2) + r with 0 <= r < ln(2), so e^x = 2^k * e^r
+    let ln_2 = T::from_num(consts::LN_2);
+    let k = x.checked_div(ln_2)?.int().checked_to_num::<u32>()?;
+    if k >= T::INT_NBITS {
+        return None;
+    }
+    let r = x - T::from_num(k) * ln_2;
+
+    // Taylor series: e^r = 1 + r + r^2 / 2! + r^3 / 3! + ...
+    let mut sum = T::one();
+    let mut term = T::one();
+    let mut n = T::Bits::one();
+
+    loop {
+        term = term * r / n;
+        if term.is_zero() {
+            break;
+        }
+        sum += term;
+        n += T::Bits::one();
+    }
+
+    if sum.to_bits().leading_zeros() < k {
+        None
+    } else {
+        Some(sum << k)
+    }
+}
+
+fn ln<T>(x: T) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    if x <= T::ZERO || (!T::IS_SIGNED && x < T::one()) {
+        return None;
+    }
+
+    // range reduction: x = m * 2^e with 1 <= m < 2, so ln(x) = ln(m) + e * ln(2)
+    let int_bits = (T::NUM_BITS - x.to_bits().leading_zeros()) as i32 - 1;
+    let e = int_bits - T::FRAC_NBITS as i32;
+    let m = if e >= 0 { x >> e as u32 } else { x << (-e) as u32 };
+
+    // atanh series: ln(m) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) with z = (m - 1) / (m + 1)
+    let z = (m - T::one()) / (m + T::one());
+    let z_2 = z * z;
+    let mut sum = T::ZERO;
+    let mut term = z;
+    let mut n = T::Bits::one();
+
+    loop {
+        let next = term / n;
+        if next.is_zero() {
+            break;
         }
+        sum += next;
+        term *= z_2;
+        n += T::Bits::one() + T::Bits::one();
     }
+
+    let ln_2 = T::from_num(consts::LN_2);
+    T::checked_from_num(e)?.checked_mul(ln_2)?.checked_add(sum << 1)
+}
+
+fn log_base<T>(x: T, base: T) -> Option<T>
+where
+    T: Fixed + Helper,
+    T::Bits: PrimInt,
+{
+    ln(x)?.checked_div(ln(base)?)
 }
 
 macro_rules! impl_fixed_pow {
@@ -160,17 +491,97 @@ macro_rules! impl_fixed_pow {
                     );
                 }
 
+                self.powf_rounded(n, Rounding::Truncate).unwrap_or_else(|| {
+                    panic!("cannot raise `{}` to the power of `{}` because the result overflows", self, n)
+                })
+            }
+
+            fn powf_down(self, n: Self) -> Option<Self> {
+                self.powf_rounded(n, Rounding::Down)
+            }
+
+            fn powf_up(self, n: Self) -> Option<Self> {
+                self.powf_rounded(n, Rounding::Up)
+            }
+
+            fn powi(self, n: i32) -> Option<Self> {
+                let one = Self::from_bits(1 << Frac::U32);
+
+                if n == 0 || self == one {
+                    return Some(one);
+                }
+
+                let x = if n < 0 { one.checked_div(self)? } else { self };
+                match n.checked_abs() {
+                    Some(n) => powi(x, n, Rounding::Truncate),
+                    // |i32::MIN| is one more than i32::MAX
+                    None => mul(powi(x, i32::MAX, Rounding::Truncate)?, x, Rounding::Truncate),
+                }
+            }
+
+            fn nth_root(self, n: u32) -> Option<Self> {
+                nth_root(self, n)
+            }
+        }
+
+        impl<Frac> PowfRounded for $fixed<Frac>
+        where
+            Frac: $le_eq + IsLessOrEqual<$le_eq_one, Output = True>,
+        {
+            fn powf_rounded(self, n: Self, rounding: Rounding) -> Option<Self> {
+                let zero = Self::from_bits(0);
+
+                if !<LeEq<Frac, $le_eq_one>>::BOOL && n <= zero {
+                    return None;
+                }
+
                 match n.cmp(&zero) {
-                    Ordering::Greater => powf(self, n),
-                    Ordering::Equal => Self::from_bits(1 << Frac::U32),
-                    Ordering::Less => powf(Self::from_bits(1 << Frac::U32) / self, Helper::neg(n)),
+                    Ordering::Greater => powf(self, n, rounding),
+                    Ordering::Equal => Some(Self::from_bits(1 << Frac::U32)),
+                    Ordering::Less => {
+                        let inverse = Self::from_bits(1 << Frac::U32).checked_div(self)?;
+                        let inverse = match rounding {
+                            Rounding::Truncate | Rounding::Down => inverse,
+                            Rounding::Up => inverse.checked_add(Self::DELTA)?,
+                        };
+                        powf(inverse, Helper::neg(n), rounding)
+                    }
                 }
             }
         }
     };
 }
 
+trait PowfRounded: Sized {
+    fn powf_rounded(self, n: Self, rounding: Rounding) -> Option<Self>;
+}
+
 impl_fixed_pow!(FixedU64, LeEqU64, U63);
+impl_fixed_pow!(FixedU128, LeEqU128, U127);
+
+macro_rules! impl_fixed_exp {
+    ($fixed:ident, $le_eq:ident, $le_eq_one:ident) => {
+        impl<Frac> FixedExp for $fixed<Frac>
+        where
+            Frac: $le_eq + IsLessOrEqual<$le_eq_one, Output = True>,
+        {
+            fn exp(self) -> Option<Self> {
+                exp(self)
+            }
+
+            fn ln(self) -> Option<Self> {
+                ln(self)
+            }
+
+            fn log_base(self, base: Self) -> Option<Self> {
+                log_base(self, base)
+            }
+        }
+    };
+}
+
+impl_fixed_exp!(FixedU64, LeEqU64, U63);
+impl_fixed_exp!(FixedU128, LeEqU128, U127);
 
 trait Helper {
     const NUM_BITS: u32;
@@ -225,12 +636,13 @@ macro_rules! impl_sign_helper {
 }
 
 impl_sign_helper!(unsigned, FixedU64, LeEqU64, U63);
+impl_sign_helper!(unsigned, FixedU128, LeEqU128, U127);
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use fixed::types::U34F30;
+    use fixed::types::{U34F30, U88F40};
 
     fn delta<T: Fixed>(a: T, b: T) -> T {
         Ord::max(a, b) - Ord::min(a, b)
@@ -262,4 +674,266 @@ mod tests {
             assert!(delta(powf_float(x, n), x.powf(n)) < epsilon);
         }
     }
+
+    #[test]
+    fn test_powf_rounding() {
+        let epsilon = U34F30::from_num(0.0001);
+
+        let test_cases = &[
+            (U34F30::from_num(1.0), U34F30::from_num(7.2)),
+            (U34F30::from_num(0.8), U34F30::from_num(4.5)),
+            (U34F30::from_num(1.2), U34F30::from_num(5.0)),
+            (U34F30::from_num(2.6), U34F30::from_num(6.7)),
+            (U34F30::from_num(1.6), U34F30::from_num(0.1)),
+            (U34F30::from_num(5.9), U34F30::from_num(0.3)),
+            (U34F30::from_num(3.1), U34F30::from_num(0.5)),
+            (U34F30::from_num(9.4), U34F30::from_num(0.7)),
+            (U34F30::from_num(0.7), U34F30::from_num(0.9)),
+            (U34F30::from_num(2.0), U34F30::from_num(0.5)),
+            (U34F30::from_num(0.999999999), U34F30::from_num(8.999999999)),
+            (U34F30::from_num(4000000000.0), U34F30::from_num(0.111111111)),
+            (U34F30::from_num(1.00001), U34F30::from_num(0.3)),
+            (U34F30::from_num(0.99999), U34F30::from_num(0.7)),
+            (U34F30::from_num(1.0000001), U34F30::from_num(2.5)),
+            (U34F30::from_num(0.999999999), U34F30::from_num(9.9)),
+        ];
+
+        for &(x, n) in test_cases {
+            let exact: f64 = x.to_num::<f64>().powf(n.to_num());
+            let down = x.powf_down(n).unwrap();
+            let up = x.powf_up(n).unwrap();
+
+            assert!(down.to_num::<f64>() <= exact * (1.0 + 1e-12));
+            assert!(up.to_num::<f64>() >= exact * (1.0 - 1e-12));
+            assert!(down <= x.powf(n) && x.powf(n) <= up);
+            assert!(delta(down, up) < epsilon);
+        }
+
+        let x = U34F30::from_num(4.0);
+        assert_eq!(x.powf_down(U34F30::from_num(1.5)), Some(U34F30::from_num(8.0)));
+        assert_eq!(x.powf_up(U34F30::ZERO), Some(U34F30::ONE));
+        assert_eq!(U34F30::ZERO.powf_up(x), Some(U34F30::ZERO));
+        for n in [U34F30::ZERO, U34F30::from_num(0.3), U34F30::from_num(7.2), U34F30::MAX] {
+            assert_eq!(U34F30::ONE.powf_down(n), Some(U34F30::ONE));
+            assert_eq!(U34F30::ONE.powf_up(n), Some(U34F30::ONE));
+            assert_eq!(U34F30::ONE.powf(n), U34F30::ONE);
+        }
+        assert_eq!(U34F30::MAX.powf_down(U34F30::from_num(2.0)), None);
+    }
+
+    #[test]
+    fn test_powf_rounding_u128() {
+        let test_cases = &[
+            (U88F40::from_num(4000000000.0), U88F40::from_num(0.148)),
+            (U88F40::from_num(0.8), U88F40::from_num(4.5)),
+            (U88F40::from_num(2.6), U88F40::from_num(6.7)),
+            (U88F40::from_num(0.999999815), U88F40::from_num(2.333333333)),
+            (U88F40::from_num(1.0000001), U88F40::from_num(0.3)),
+        ];
+
+        for &(x, n) in test_cases {
+            let exact: f64 = x.to_num::<f64>().powf(n.to_num());
+            let down = x.powf_down(n).unwrap();
+            let up = x.powf_up(n).unwrap();
+
+            assert!(down.to_num::<f64>() <= exact * (1.0 + 1e-15));
+            assert!(up.to_num::<f64>() >= exact * (1.0 - 1e-15));
+            assert!(down <= x.powf(n) && x.powf(n) <= up);
+            assert!(delta(down, up).to_num::<f64>() < exact * 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_powi() {
+        let x = U34F30::from_num(2.0);
+        assert_eq!(x.powi(10), Some(U34F30::from_num(1024.0)));
+        assert_eq!(x.powi(1), Some(x));
+        assert_eq!(x.powi(0), Some(U34F30::ONE));
+        assert_eq!(x.powi(-1), Some(U34F30::from_num(0.5)));
+        assert_eq!(x.powi(-2), Some(U34F30::from_num(0.25)));
+        assert_eq!(x.powi(-30), Some(U34F30::DELTA));
+        assert_eq!(x.powi(-31), Some(U34F30::ZERO));
+        assert_eq!(x.powi(33), Some(U34F30::from_num(1u64 << 33)));
+        assert_eq!(x.powi(34), None);
+
+        assert_eq!(U34F30::ZERO.powi(0), Some(U34F30::ONE));
+        assert_eq!(U34F30::ZERO.powi(3), Some(U34F30::ZERO));
+        assert_eq!(U34F30::ZERO.powi(-1), None);
+        assert_eq!(U34F30::ONE.powi(i32::MAX), Some(U34F30::ONE));
+        assert_eq!(U34F30::ONE.powi(i32::MIN), Some(U34F30::ONE));
+        assert_eq!(x.powi(i32::MIN), Some(U34F30::ZERO));
+        assert_eq!(U34F30::from_num(0.5).powi(i32::MIN), None);
+
+        // agrees with powf on integer powers
+        for (x, n) in [(1.5, 7), (0.8, 4), (3.1, 5), (9.4, 2)] {
+            let x = U34F30::from_num(x);
+            assert_eq!(x.powi(n), Some(x.powf(U34F30::from_num(n))));
+        }
+    }
+
+    #[test]
+    fn test_nth_root() {
+        let epsilon = U34F30::DELTA;
+
+        assert_eq!(U34F30::from_num(27.0).nth_root(3), Some(U34F30::from_num(3.0)));
+        assert_eq!(U34F30::from_num(16.0).nth_root(4), Some(U34F30::from_num(2.0)));
+        assert_eq!(U34F30::from_num(1024.0).nth_root(10), Some(U34F30::from_num(2.0)));
+        assert_eq!(U34F30::from_num(0.125).nth_root(3), Some(U34F30::from_num(0.5)));
+        assert_eq!(U34F30::ZERO.nth_root(5), Some(U34F30::ZERO));
+        assert_eq!(U34F30::ONE.nth_root(7), Some(U34F30::ONE));
+        assert_eq!(U34F30::from_num(27.0).nth_root(0), None);
+        assert_eq!(U34F30::from_num(27.0).nth_root(u32::MAX), None);
+
+        for x in [0.0001, 0.3, 1.7, 2.0, 27.5, 4000000000.0, 17179869183.0] {
+            let x = U34F30::from_num(x);
+            assert_eq!(x.nth_root(1), Some(x));
+            assert!(delta(x.nth_root(2).unwrap(), x.sqrt()) <= epsilon);
+
+            for n in [3, 4, 5, 9] {
+                let root = x.nth_root(n).unwrap();
+                let exact = x.to_num::<f64>().powf(1.0 / n as f64);
+                assert!(delta(root, U34F30::from_num(exact)) <= epsilon);
+                assert!(root.powi(n as i32).unwrap() <= x);
+            }
+        }
+    }
+
+    fn sqrt_bisection<T>(x: T) -> T
+    where
+        T: Fixed + Helper,
+        T::Bits: PrimInt,
+    {
+        if x.is_zero() || x.is_one() {
+            return x;
+        }
+
+        let mut pow2 = T::one();
+        let mut result;
+
+        if x < T::one() {
+            while x <= pow2 * pow2 {
+                pow2 >>= 1;
+            }
+
+            result = pow2;
+        } else {
+            // x >= T::one()
+            while pow2 * pow2 <= x {
+                pow2 <<= 1;
+            }
+
+            result = pow2 >> 1;
+        }
+
+        for _ in 0..T::NUM_BITS {
+            pow2 >>= 1;
+            let next_result = result + pow2;
+            if next_result * next_result <= x {
+                result = next_result;
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_sqrt() {
+        let edge_cases = (0..61).flat_map(|shift| {
+            let bits = 1u64 << shift;
+            [bits - 1, bits, bits + 1, bits + (bits >> 1), bits * 3 - 1]
+        });
+        let random_cases = (0..10_000).scan(0x2545_f491_4f6c_dd1d_u64, |seed, _| {
+            *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            Some(*seed >> (*seed % 32 + 2))
+        });
+
+        // the bisection overflows while searching for its starting bound once x >= 2^32
+        for bits in edge_cases.chain(random_cases).filter(|&bits| bits < 1 << 62) {
+            let x = U34F30::from_bits(bits);
+            assert_eq!(sqrt(x), sqrt_bisection(x), "sqrt({})", x);
+        }
+
+        assert_eq!(sqrt(U34F30::from_num(4.0)), U34F30::from_num(2.0));
+        assert_eq!(sqrt(U34F30::from_num(81.0)), U34F30::from_num(9.0));
+        assert_eq!(sqrt(U34F30::from_num(1u64 << 32)), U34F30::from_num(1u64 << 16));
+    }
+
+    #[test]
+    fn test_exp() {
+        let epsilon = U34F30::from_num(0.0001);
+
+        let test_cases = &[
+            U34F30::from_num(0.0001),
+            U34F30::from_num(0.1),
+            U34F30::from_num(0.5),
+            U34F30::from_num(0.69),
+            U34F30::from_num(1.0),
+            U34F30::from_num(1.5),
+            U34F30::from_num(2.7),
+            U34F30::from_num(4.2),
+            U34F30::from_num(7.9),
+        ];
+
+        for &x in test_cases {
+            let exact = U34F30::from_num(x.to_num::<f64>().exp());
+            assert!(delta(exact, x.exp().unwrap()) < epsilon);
+        }
+
+        assert_eq!(U34F30::ZERO.exp(), Some(U34F30::ONE));
+        assert!(U34F30::from_num(23.0).exp().is_some());
+        assert_eq!(U34F30::from_num(24.0).exp(), None);
+        assert_eq!(U34F30::MAX.exp(), None);
+    }
+
+    #[test]
+    fn test_ln() {
+        let epsilon = U34F30::from_num(0.0001);
+
+        let test_cases = &[
+            U34F30::from_num(1.0001),
+            U34F30::from_num(1.2),
+            U34F30::from_num(1.9),
+            U34F30::from_num(2.0),
+            U34F30::from_num(2.6),
+            U34F30::from_num(9.4),
+            U34F30::from_num(1000.0),
+            U34F30::from_num(123456789.0),
+        ];
+
+        for &x in test_cases {
+            let exact = U34F30::from_num(x.to_num::<f64>().ln());
+            assert!(delta(exact, x.ln().unwrap()) < epsilon);
+        }
+
+        let e = U34F30::ONE.exp().unwrap();
+        assert!(delta(U34F30::ONE, e.ln().unwrap()) < epsilon);
+
+        assert_eq!(U34F30::ONE.ln(), Some(U34F30::ZERO));
+        assert_eq!(U34F30::ZERO.ln(), None);
+        assert_eq!(U34F30::from_num(0.5).ln(), None);
+        assert!(U34F30::MAX.ln().is_some());
+    }
+
+    #[test]
+    fn test_log_base() {
+        let epsilon = U34F30::from_num(0.0001);
+
+        let test_cases = &[
+            (U34F30::from_num(8.0), U34F30::from_num(2.0)),
+            (U34F30::from_num(1000.0), U34F30::from_num(10.0)),
+            (U34F30::from_num(1.5), U34F30::from_num(10.0)),
+            (U34F30::from_num(123456.0), U34F30::from_num(3.7)),
+            (U34F30::from_num(2.0), U34F30::from_num(8.0)),
+        ];
+
+        for &(x, base) in test_cases {
+            let exact = U34F30::from_num(x.to_num::<f64>().log(base.to_num()));
+            assert!(delta(exact, x.log_base(base).unwrap()) < epsilon);
+        }
+
+        assert_eq!(U34F30::ONE.log_base(U34F30::from_num(2.0)), Some(U34F30::ZERO));
+        assert_eq!(U34F30::from_num(8.0).log_base(U34F30::ONE), None);
+        assert_eq!(U34F30::from_num(8.0).log_base(U34F30::ZERO), None);
+        assert_eq!(U34F30::ZERO.log_base(U34F30::from_num(2.0)), None);
+    }
 }
@@ -1,7 +1,49 @@
-use bn::safe_math::CheckedMulDiv;
+use bn::safe_math::{CheckedMulDiv, Rounding};
+
+// Which side of a proportional join / exit the amounts are computed for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProportionalDirection {
+    // token amounts in for an lp amount out
+    In,
+    // token amounts out for an lp amount in
+    Out,
+}
+
+impl ProportionalDirection {
+    // The rounding that favors the pool: amounts in round up, amounts out round down
+    pub fn canonical_rounding(self) -> Rounding {
+        match self {
+            ProportionalDirection::In => Rounding::Up,
+            ProportionalDirection::Out => Rounding::Down,
+        }
+    }
+}
+
+// Per token b * amount / lpTotalSupply, rounded in the given direction on both the multiplication
+// and division. Pass `direction.canonical_rounding()` for the Balancer behavior, or the opposite
+// rounding for conservative bounds
+pub fn compute_proportional_amounts(
+    balances: &[u64],
+    pool_token_supply: u64,
+    amount: u64,
+    rounding: Rounding,
+) -> Option<Vec<u64>> {
+    if pool_token_supply == 0 {
+        return None;
+    }
+
+    balances
+        .iter()
+        .map(|balance| balance.checked_mul_div(amount, pool_token_supply, rounding))
+        .collect()
+}
 
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-utils/contracts/lib/BasePoolMath.sol#L22-L45
-pub fn compute_proportional_amounts_in(balances: &Vec<u64>, pool_token_supply: u64, amount_out: u64) -> Vec<u64> {
+pub fn compute_proportional_amounts_in(
+    balances: &Vec<u64>,
+    pool_token_supply: u64,
+    amount_out: u64,
+) -> Option<Vec<u64>> {
     /************************************************************************************
     // computeProportionalAmountsIn                                                    //
     // (per token)                                                                     //
@@ -14,16 +56,20 @@ pub fn compute_proportional_amounts_in(balances: &Vec<u64>, pool_token_supply: u
     // Since we're computing amounts in, we round up overall. This means rounding up on both the
     // multiplication and division.
 
-    let mut amounts_in: Vec<u64> = vec![];
-    for i in 0..balances.len() {
-        amounts_in.push(balances[i].checked_mul_div_up(amount_out, pool_token_supply).unwrap());
-    }
-
-    amounts_in
+    compute_proportional_amounts(
+        balances,
+        pool_token_supply,
+        amount_out,
+        ProportionalDirection::In.canonical_rounding(),
+    )
 }
 
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-utils/contracts/lib/BasePoolMath.sol#L47-L70
-pub fn compute_proportional_amounts_out(balances: &Vec<u64>, pool_token_supply: u64, amount_in: u64) -> Vec<u64> {
+pub fn compute_proportional_amounts_out(
+    balances: &Vec<u64>,
+    pool_token_supply: u64,
+    amount_in: u64,
+) -> Option<Vec<u64>> {
     /**********************************************************************************************
     // computeProportionalAmountsOut                                                             //
     // (per token)                                                                               //
@@ -36,12 +82,12 @@ pub fn compute_proportional_amounts_out(balances: &Vec<u64>, pool_token_supply:
     // Since we're computing an amount out, we round down overall. This means rounding down on both the
     // multiplication and division.
 
-    let mut amounts_out: Vec<u64> = vec![];
-    for i in 0..balances.len() {
-        amounts_out.push(balances[i].checked_mul_div_down(amount_in, pool_token_supply).unwrap());
-    }
-
-    amounts_out
+    compute_proportional_amounts(
+        balances,
+        pool_token_supply,
+        amount_in,
+        ProportionalDirection::Out.canonical_rounding(),
+    )
 }
 
 #[cfg(test)]
@@ -53,28 +99,92 @@ mod tests {
         let balances = vec![5_000_000_000, 3_000_000_000];
         let pool_token_supply = 1_000_000_000;
 
-        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 100_000_000);
+        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 100_000_000).unwrap();
         assert_eq!(amounts_in[0], 500000000);
         assert_eq!(amounts_in[1], 300000000);
 
-        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 100_000_000);
+        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 100_000_000).unwrap();
         assert_eq!(amounts_out[0], 500000000);
         assert_eq!(amounts_out[1], 300000000);
 
-        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 333_333_333);
+        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 333_333_333).unwrap();
         assert_eq!(amounts_in[0], 1666666665);
         assert_eq!(amounts_in[1], 999999999);
 
-        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 333_333_333);
+        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 333_333_333).unwrap();
         assert_eq!(amounts_out[0], 1666666665);
         assert_eq!(amounts_out[1], 999999999);
 
-        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 777_777_777);
+        let amounts_in = compute_proportional_amounts_in(&balances, pool_token_supply, 777_777_777).unwrap();
         assert_eq!(amounts_in[0], 3888888885);
         assert_eq!(amounts_in[1], 2333333331);
 
-        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 777_777_777);
+        let amounts_out = compute_proportional_amounts_out(&balances, pool_token_supply, 777_777_777).unwrap();
         assert_eq!(amounts_out[0], 3888888885);
         assert_eq!(amounts_out[1], 2333333331);
     }
+
+    #[test]
+    fn test_compute_proportional_amounts_rounding() {
+        let balances = vec![5_000_000_000, 3_000_000_000, 7, 0];
+        let pool_token_supply = 1_000_000_007;
+
+        for amount in [0, 1, 100_000_000, 333_333_333, 777_777_777, pool_token_supply] {
+            let up = compute_proportional_amounts(&balances, pool_token_supply, amount, Rounding::Up).unwrap();
+            let down = compute_proportional_amounts(&balances, pool_token_supply, amount, Rounding::Down).unwrap();
+            for (up, down) in up.iter().zip(down.iter()) {
+                assert!(up >= down);
+                assert!(up - down <= 1);
+            }
+        }
+
+        // the canonical wrappers match their direction's canonical rounding
+        for amount in [1, 333_333_333, 777_777_777] {
+            assert_eq!(
+                compute_proportional_amounts_in(&balances, pool_token_supply, amount),
+                compute_proportional_amounts(
+                    &balances,
+                    pool_token_supply,
+                    amount,
+                    ProportionalDirection::In.canonical_rounding()
+                )
+            );
+            assert_eq!(
+                compute_proportional_amounts_out(&balances, pool_token_supply, amount),
+                compute_proportional_amounts(
+                    &balances,
+                    pool_token_supply,
+                    amount,
+                    ProportionalDirection::Out.canonical_rounding()
+                )
+            );
+        }
+
+        // the opposite rounding gives the conservative bounds
+        assert_eq!(
+            compute_proportional_amounts(&balances, pool_token_supply, 1, Rounding::Down),
+            Some(vec![4, 2, 0, 0])
+        );
+        assert_eq!(
+            compute_proportional_amounts(&balances, pool_token_supply, 1, Rounding::Up),
+            Some(vec![5, 3, 1, 0])
+        );
+        assert_eq!(compute_proportional_amounts(&balances, 0, 1, Rounding::Up), None);
+    }
+
+    #[test]
+    fn test_compute_proportional_amounts_invalid() {
+        let balances = vec![5_000_000_000, 3_000_000_000];
+
+        assert_eq!(compute_proportional_amounts_in(&balances, 0, 100_000_000), None);
+        assert_eq!(compute_proportional_amounts_out(&balances, 0, 100_000_000), None);
+
+        let balances = vec![u64::MAX, 3_000_000_000];
+        assert_eq!(compute_proportional_amounts_in(&balances, 1, 2), None);
+        assert_eq!(compute_proportional_amounts_out(&balances, 1, 2), None);
+        assert_eq!(
+            compute_proportional_amounts_out(&balances, 2, 1),
+            Some(vec![u64::MAX / 2, 1_500_000_000])
+        );
+    }
 }
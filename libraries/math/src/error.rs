@@ -16,6 +16,18 @@ pub enum WeightedMathError {
 
     #[error("MaxInvariantRatio")]
     MaxInvariantRatio,
+
+    #[error("InvalidWeights")]
+    InvalidWeights,
+
+    #[error("Overflow")]
+    Overflow,
+
+    #[error("Zero pool token supply")]
+    ZeroPoolTokenSupply,
+
+    #[error("InvalidAmounts")]
+    InvalidAmounts,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
@@ -25,4 +37,51 @@ pub enum StableMathError {
 
     #[error("Get balance didnt converge")]
     GetBalanceDidntConverge,
+
+    #[error("Overflow")]
+    Overflow,
+
+    #[error("Zero balance")]
+    ZeroBalance,
+
+    #[error("Max safe balance")]
+    MaxSafeBalance,
+
+    #[error("Zero pool token supply")]
+    ZeroPoolTokenSupply,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum ScalingError {
+    #[error("InvalidScalingFactor")]
+    InvalidScalingFactor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum PoolError {
+    #[error("InvalidTokenCount")]
+    InvalidTokenCount,
+
+    #[error("InvalidSwapFee")]
+    InvalidSwapFee,
+
+    #[error("InvalidWeights")]
+    InvalidWeights,
+
+    #[error("InvalidAmplification")]
+    InvalidAmplification,
+
+    #[error("InvalidScalingFactor")]
+    InvalidScalingFactor,
+
+    #[error("DuplicateMint")]
+    DuplicateMint,
+}
+
+impl From<ScalingError> for PoolError {
+    fn from(error: ScalingError) -> Self {
+        match error {
+            ScalingError::InvalidScalingFactor => PoolError::InvalidScalingFactor,
+        }
+    }
 }
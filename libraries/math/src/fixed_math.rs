@@ -1,19 +1,36 @@
-use bn::safe_math::CheckedMulDiv;
-use fixed::types::U34F30;
-use fixed_exp::FixedPowF;
+use bn::safe_math::{CheckedMulDiv, Rounding};
+use bn::{uint192, U192};
+use fixed::types::{U34F30, U88F40};
+use fixed_exp::{FixedExp, FixedPowF};
 
 pub const ZERO: u64 = 0;
 
+pub const HALF: u64 = 500_000_000;
+
 pub const ONE: u64 = 1_000_000_000;
 
 pub const TWO: u64 = 2_000_000_000;
 
 pub const FOUR: u64 = 4_000_000_000;
 
+// Decimal digits of the fixed point values, ONE is 10^SCALE
 pub const SCALE: u32 = 9;
 
+pub const ONE_U192: U192 = U192([ONE, 0, 0]);
+
+// ONE in the binary fixed point types exp/ln and pow run on, i.e. 1 << Frac of U34F30 and U88F40. A variant at
+// another precision has to pick a type whose integer bits still hold its largest values and change both
+// together, which the assertions below check at compile time.
 pub const BITS_ONE: u64 = 1073741824; // 1 << 30
 
+// pow needs the wider type, at 30 fractional bits every power loses up to ~4e-8 while a weighted invariant
+// multiplies one per token. 40 bits still leave bases within ~1e-6 of ONE to the first-order fast path
+pub const POW_BITS_ONE: u128 = 1099511627776; // 1 << 40
+
+const _: () = assert!(ONE == 10u64.pow(SCALE));
+const _: () = assert!(BITS_ONE == 1 << U34F30::FRAC_NBITS);
+const _: () = assert!(POW_BITS_ONE == 1 << U88F40::FRAC_NBITS);
+
 pub trait FixedPow<RHS = Self> {
     /// Output type for the methods of this trait.
     type Output;
@@ -21,6 +38,16 @@ pub trait FixedPow<RHS = Self> {
     fn pow_down(self, rhs: RHS) -> Self::Output;
 
     fn pow_up(self, rhs: RHS) -> Self::Output;
+
+    fn pow_rounded(self, rhs: RHS, rounding: Rounding) -> Self::Output
+    where
+        Self: Sized,
+    {
+        match rounding {
+            Rounding::Down => self.pow_down(rhs),
+            Rounding::Up => self.pow_up(rhs),
+        }
+    }
 }
 
 pub trait FixedMul<RHS = Self> {
@@ -30,6 +57,16 @@ pub trait FixedMul<RHS = Self> {
     fn mul_down(self, rhs: RHS) -> Self::Output;
 
     fn mul_up(self, rhs: RHS) -> Self::Output;
+
+    fn mul_rounded(self, rhs: RHS, rounding: Rounding) -> Self::Output
+    where
+        Self: Sized,
+    {
+        match rounding {
+            Rounding::Down => self.mul_down(rhs),
+            Rounding::Up => self.mul_up(rhs),
+        }
+    }
 }
 
 pub trait FixedDiv<RHS = Self> {
@@ -39,6 +76,30 @@ pub trait FixedDiv<RHS = Self> {
     fn div_down(self, rhs: RHS) -> Self::Output;
 
     fn div_up(self, rhs: RHS) -> Self::Output;
+
+    fn div_rounded(self, rhs: RHS, rounding: Rounding) -> Self::Output
+    where
+        Self: Sized,
+    {
+        match rounding {
+            Rounding::Down => self.div_down(rhs),
+            Rounding::Up => self.div_up(rhs),
+        }
+    }
+}
+
+pub trait FixedAdd<RHS = Self> {
+    /// Output type for the methods of this trait.
+    type Output;
+
+    fn add(self, rhs: RHS) -> Self::Output;
+}
+
+pub trait FixedSub<RHS = Self> {
+    /// Output type for the methods of this trait.
+    type Output;
+
+    fn sub(self, rhs: RHS) -> Self::Output;
 }
 
 pub trait FixedComplement<RHS = Self> {
@@ -46,48 +107,74 @@ pub trait FixedComplement<RHS = Self> {
     type Output;
 
     fn complement(self) -> Self::Output;
+
+    /// Like `complement`, but `None` for inputs over ONE instead of saturating to zero.
+    fn checked_complement(self) -> Option<Self::Output>;
 }
 
 impl FixedPow for u64 {
     type Output = u64;
-    // Optimize for when y equals 1.0, 2.0 or 4.0, as those are very simple to implement and occur often in 50/50
-    // and 80/20 Weighted Pools
 
     fn pow_down(self, rhs: Self) -> Self::Output {
-        match rhs {
-            ZERO => ONE,
-            ONE => self,
-            TWO => self.mul_down(self),
-            FOUR => {
-                let square = self.mul_down(self);
-                square.mul_down(square)
-            }
-            _ => {
-                let base = U34F30::from_bits(self.mul_down(BITS_ONE));
-                let exp = U34F30::from_bits(rhs.mul_down(BITS_ONE));
-                base.powf(exp).to_bits().div_down(BITS_ONE)
-            }
-        }
+        pow(self, rhs, Rounding::Down).unwrap()
     }
 
     fn pow_up(self, rhs: Self) -> Self::Output {
-        match rhs {
-            ZERO => ONE,
-            ONE => self,
-            TWO => self.mul_up(self),
-            FOUR => {
-                let square = self.mul_up(self);
-                square.mul_up(square)
-            }
-            _ => {
-                let base = U34F30::from_bits(self.mul_up(BITS_ONE));
-                let exp = U34F30::from_bits(rhs.mul_up(BITS_ONE));
-                base.powf(exp).to_bits().div_up(BITS_ONE)
-            }
+        pow(self, rhs, Rounding::Up).unwrap()
+    }
+}
+
+/// power rounded in the given direction, returns `None` on overflow
+// A free function since the inherent `u64::pow` would shadow a method of the same name
+pub fn pow(base: u64, exp: u64, rounding: Rounding) -> Option<u64> {
+    // Optimize for when y equals 0.5, 1.0, 2.0 or 4.0, as those are very simple to implement and occur often in
+    // 50/50 and 80/20 Weighted Pools
+    match exp {
+        ZERO => Some(ONE),
+        HALF => {
+            // sqrt(x / ONE) * ONE = sqrt(x * ONE), which is exact up to the final integer root
+            let square = uint192!(base) * ONE_U192;
+            let root = square.integer_sqrt();
+            let root = match rounding {
+                Rounding::Up if root * root < square => root + 1,
+                _ => root,
+            };
+            Some(root.as_u64())
+        }
+        ONE => Some(base),
+        TWO => base.checked_mul_div(base, ONE, rounding),
+        FOUR => {
+            let square = base.checked_mul_div(base, ONE, rounding)?;
+            square.checked_mul_div(square, ONE, rounding)
+        }
+        _ => {
+            // A smaller exponent only gives a smaller power when the base is at least one
+            let exp_rounding = match (base >= ONE, rounding) {
+                (true, rounding) => rounding,
+                (false, Rounding::Down) => Rounding::Up,
+                (false, Rounding::Up) => Rounding::Down,
+            };
+            let base = U88F40::from_bits(to_pow_bits(base, rounding)?);
+            let exp = U88F40::from_bits(to_pow_bits(exp, exp_rounding)?);
+            let power = match rounding {
+                Rounding::Down => base.powf_down(exp)?,
+                Rounding::Up => base.powf_up(exp)?,
+            };
+            from_pow_bits(power.to_bits(), rounding)
         }
     }
 }
 
+fn to_pow_bits(value: u64, rounding: Rounding) -> Option<u128> {
+    let bits = uint192!(value).checked_mul_div(uint192!(POW_BITS_ONE), ONE_U192, rounding)?;
+    Some(bits.as_u128())
+}
+
+fn from_pow_bits(bits: u128, rounding: Rounding) -> Option<u64> {
+    let value = uint192!(bits).checked_mul_div(ONE_U192, uint192!(POW_BITS_ONE), rounding)?;
+    u64::try_from(value.as_u128()).ok()
+}
+
 impl FixedMul for u64 {
     type Output = u64;
 
@@ -112,16 +199,110 @@ impl FixedDiv for u64 {
     }
 }
 
+impl FixedPow for U192 {
+    type Output = Option<U192>;
+
+    // pow works on u64 at the ONE scale, so a base or exponent past u64 is out of its range, which is reported
+    // like an overflow of the result
+    fn pow_down(self, rhs: Self) -> Self::Output {
+        pow_u192(self, rhs, Rounding::Down)
+    }
+
+    fn pow_up(self, rhs: Self) -> Self::Output {
+        pow_u192(self, rhs, Rounding::Up)
+    }
+}
+
+fn pow_u192(base: U192, exp: U192, rounding: Rounding) -> Option<U192> {
+    let max = uint192!(u64::MAX);
+    if base > max || exp > max {
+        return None;
+    }
+    pow(base.as_u64(), exp.as_u64(), rounding).map(|power| uint192!(power))
+}
+
+impl FixedMul for U192 {
+    type Output = U192;
+
+    fn mul_down(self, rhs: Self) -> Self::Output {
+        self.checked_mul_div_down(rhs, ONE_U192).unwrap()
+    }
+
+    fn mul_up(self, rhs: Self) -> Self::Output {
+        self.checked_mul_div_up(rhs, ONE_U192).unwrap()
+    }
+}
+
+impl FixedDiv for U192 {
+    type Output = U192;
+
+    fn div_down(self, rhs: Self) -> Self::Output {
+        self.checked_mul_div_down(ONE_U192, rhs).unwrap()
+    }
+
+    fn div_up(self, rhs: Self) -> Self::Output {
+        self.checked_mul_div_up(ONE_U192, rhs).unwrap()
+    }
+}
+
+/// e^x, returns `None` on overflow
+pub fn exp(x: u64) -> Option<u64> {
+    let x = U34F30::from_bits(x.checked_mul_div_down(BITS_ONE, ONE)?);
+    x.exp()?.to_bits().checked_mul_div_down(ONE, BITS_ONE)
+}
+
+/// natural logarithm of x, returns `None` below ONE where the result would be negative
+pub fn ln(x: u64) -> Option<u64> {
+    let x = U34F30::from_bits(x.checked_mul_div_down(BITS_ONE, ONE)?);
+    x.ln()?.to_bits().checked_mul_div_down(ONE, BITS_ONE)
+}
+
+/// `amount` with `from_scale` decimals at `to_scale` decimals, rounding down when digits are dropped. Returns
+/// `None` if the result overflows
+pub fn rescale(amount: u64, from_scale: u32, to_scale: u32) -> Option<u64> {
+    if to_scale >= from_scale {
+        amount.checked_mul(10u64.checked_pow(to_scale - from_scale)?)
+    } else {
+        // past 10^19 every u64 rounds down to zero
+        Some(
+            10u64
+                .checked_pow(from_scale - to_scale)
+                .map_or(0, |divisor| amount / divisor),
+        )
+    }
+}
+
+// Values share the same scale, so these are the plain checked integer ops
+impl FixedAdd for u64 {
+    type Output = Option<u64>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs)
+    }
+}
+
+impl FixedSub for u64 {
+    type Output = Option<u64>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs)
+    }
+}
+
 impl FixedComplement for u64 {
     type Output = u64;
 
     fn complement(self) -> Self::Output {
         ONE.saturating_sub(self)
     }
+
+    fn checked_complement(self) -> Option<Self::Output> {
+        ONE.checked_sub(self)
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::weighted_math::MAX_SAFE_BALANCE;
 
@@ -161,6 +342,24 @@ mod tests {
         888_888_888,
     ];
 
+    #[test]
+    fn test_rescale() {
+        assert_eq!(rescale(ONE, SCALE, 18), Some(1_000_000_000_000_000_000));
+        assert_eq!(rescale(1_000_000_000_000_000_000, 18, SCALE), Some(ONE));
+        assert_eq!(rescale(HALF, SCALE, SCALE), Some(HALF));
+        assert_eq!(rescale(0, 0, 19), Some(0));
+
+        // dropped digits round down
+        assert_eq!(rescale(1_999_999_999_999_999_999, 18, SCALE), Some(1_999_999_999));
+        assert_eq!(rescale(999_999_999, SCALE, 0), Some(0));
+        assert_eq!(rescale(u64::MAX, 20, 0), Some(0));
+
+        assert_eq!(rescale(19, 0, 18), None);
+        assert_eq!(rescale(1, 0, 20), None);
+        assert_eq!(rescale(u64::MAX, SCALE, SCALE + 1), None);
+        assert_eq!(rescale(18, 0, 18), Some(18_000_000_000_000_000_000));
+    }
+
     #[test]
     fn test_powers_for_invariant() {
         for normalized_weight in AVAILABLE_WEIGHTS.clone() {
@@ -198,6 +397,169 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pow_half() {
+        for base in [
+            MIN_INVARIANT_RATIO,
+            HALF,
+            ONE,
+            TWO,
+            1_234_567_891,
+            MAX_INVARIANT_RATIO,
+            MAX_SAFE_BALANCE,
+            u64::MAX,
+        ] {
+            let value = ((base as f64 / 1e9).sqrt() * 1e9) as u64;
+            let value_1 = base.pow_down(HALF);
+            let value_2 = base.pow_up(HALF);
+            check_epsilon(value, value_1);
+            check_epsilon(value, value_2);
+            assert!(value_2 >= value_1);
+            assert!(value_2 - value_1 <= 1);
+        }
+
+        // perfect squares have an exact root either way
+        assert_eq!(ONE.pow_down(HALF), ONE);
+        assert_eq!((4 * ONE).pow_down(HALF), TWO);
+        assert_eq!((4 * ONE).pow_up(HALF), TWO);
+        assert_eq!(ZERO.pow_up(HALF), ZERO);
+        // tiny bases are only off by the final integer root
+        assert_eq!(2.pow_down(HALF), 44_721);
+        assert_eq!(2.pow_up(HALF), 44_722);
+    }
+
+    #[test]
+    fn test_rounded() {
+        let (a, b) = (1_234_567_891, 333_333_333);
+        assert_eq!(a.mul_rounded(b, Rounding::Down), a.mul_down(b));
+        assert_eq!(a.mul_rounded(b, Rounding::Up), a.mul_up(b));
+        assert_eq!(a.div_rounded(b, Rounding::Down), a.div_down(b));
+        assert_eq!(a.div_rounded(b, Rounding::Up), a.div_up(b));
+        assert_eq!(a.pow_rounded(b, Rounding::Down), a.pow_down(b));
+        assert_eq!(a.pow_rounded(b, Rounding::Up), a.pow_up(b));
+        assert!(a.mul_rounded(b, Rounding::Up) > a.mul_rounded(b, Rounding::Down));
+    }
+
+    #[test]
+    fn test_add_sub() {
+        assert_eq!(ONE.add(ONE), Some(TWO));
+        assert_eq!(TWO.add(TWO), Some(FOUR));
+        assert_eq!(FOUR.sub(ONE), Some(3 * ONE));
+        assert_eq!(ONE.sub(ONE), Some(ZERO));
+        assert_eq!(ONE.sub(ONE + 1), None);
+        assert_eq!(u64::MAX.add(1), None);
+        assert_eq!(u64::MAX.sub(u64::MAX), Some(ZERO));
+        assert_eq!(ONE.sub(300_000_000), Some(300_000_000.complement()));
+    }
+
+    #[test]
+    fn test_checked_complement() {
+        assert_eq!(ZERO.checked_complement(), Some(ONE));
+        assert_eq!(300_000_000.checked_complement(), Some(700_000_000));
+        assert_eq!(ONE.checked_complement(), Some(ZERO));
+        assert_eq!((ONE + 1).checked_complement(), None);
+        assert_eq!((ONE + 1).complement(), ZERO);
+    }
+
+    #[test]
+    fn test_u192() {
+        assert_eq!(ONE_U192, uint192!(ONE));
+
+        let values = [1, 300_000_000, ONE, 1_234_567_891, 7_777_777_777, 1_000_000_000_000];
+        for a in values {
+            for b in values {
+                let (x, y) = (uint192!(a), uint192!(b));
+                assert_eq!(x.mul_down(y), uint192!(a.mul_down(b)));
+                assert_eq!(x.mul_up(y), uint192!(a.mul_up(b)));
+                // a tiny divisor overflows u64 but not U192
+                match a.checked_mul_div_down(ONE, b) {
+                    Some(_) => {
+                        assert_eq!(x.div_down(y), uint192!(a.div_down(b)));
+                        assert_eq!(x.div_up(y), uint192!(a.div_up(b)));
+                    }
+                    None => assert!(x.div_down(y) > uint192!(u64::MAX)),
+                }
+            }
+        }
+        for base in [300_000_000, MAX_INVARIANT_RATIO, ONE, 1_234_567_891, 2_500_000_000] {
+            for exp in [ZERO, ONE, TWO, FOUR, 333_333_333, 1_500_000_000] {
+                assert_eq!(
+                    uint192!(base).pow_down(uint192!(exp)),
+                    Some(uint192!(base.pow_down(exp)))
+                );
+                assert_eq!(uint192!(base).pow_up(uint192!(exp)), Some(uint192!(base.pow_up(exp))));
+            }
+        }
+        // inputs past u64 and results that overflow come back as None instead of panicking
+        let past_u64 = uint192!(u64::MAX) + 1;
+        assert_eq!(past_u64.pow_down(uint192!(ONE)), None);
+        assert_eq!(uint192!(TWO).pow_up(past_u64), None);
+        assert_eq!(uint192!(u64::MAX).pow_down(uint192!(TWO)), None);
+        assert_eq!(uint192!(u64::MAX).pow_up(uint192!(ONE)), Some(uint192!(u64::MAX)));
+
+        // intermediate products past u64 stay exact
+        let balance = uint192!(MAX_SAFE_BALANCE);
+        let ratio = balance.mul_down(uint192!(5 * ONE));
+        assert_eq!(ratio, uint192!(MAX_SAFE_BALANCE) * 5);
+        assert_eq!(ratio.mul_down(uint192!(100_000_000)).as_u64(), MAX_SAFE_BALANCE / 2);
+        assert_eq!(ratio.div_up(uint192!(5 * ONE)).as_u64(), MAX_SAFE_BALANCE);
+    }
+
+    #[test]
+    fn test_exp_ln() {
+        assert_eq!(exp(ZERO), Some(ONE));
+        assert_eq!(ln(ONE), Some(ZERO));
+        assert_eq!(ln(ONE - 1), None);
+        assert_eq!(ln(ZERO), None);
+        assert_eq!(exp(24 * ONE), None);
+
+        for x in [1_000_000, 100_000_000, 693_147_180, ONE, TWO, 5_555_555_555, 20 * ONE] {
+            let value = ((x as f64 / 1e9).exp() * 1e9) as u64;
+            check_epsilon(value, exp(x).unwrap());
+        }
+        for x in [TWO, 2_718_281_828, 7_777_777_777, 1_000 * ONE, 1_000_000 * ONE] {
+            let value = ((x as f64 / 1e9).ln() * 1e9) as u64;
+            check_epsilon(value, ln(x).unwrap());
+        }
+
+        // x^y as e^(y * ln x)
+        for base in [1_500_000_000, TWO, 7_777_777_777] {
+            for y in [333_333_333, 1_500_000_000, 2_500_000_000] {
+                check_epsilon(base.pow_down(y), exp(ln(base).unwrap().mul_down(y)).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_pow_overflow() {
+        for rounding in [Rounding::Down, Rounding::Up] {
+            assert_eq!(pow(u64::MAX, ZERO, rounding), Some(ONE));
+            assert_eq!(pow(u64::MAX, ONE, rounding), Some(u64::MAX));
+            assert_eq!(pow(u64::MAX, TWO, rounding), None);
+            assert_eq!(pow(u64::MAX, FOUR, rounding), None);
+            assert_eq!(pow(u64::MAX, 1_500_000_000, rounding), None);
+        }
+        assert_eq!(pow(TWO, FOUR, Rounding::Down), Some(16 * ONE));
+        assert_eq!(pow(TWO, FOUR, Rounding::Up), Some(16 * ONE));
+    }
+
+    #[test]
+    fn test_pow_near_one() {
+        // bases within 2^-20 of ONE take the first-order fast path in powf_01 instead of the sqrt loop
+        for (base, exp) in [
+            (999_999_815, 2_333_333_333),
+            (1_000_000_185, 2_333_333_333),
+            (999_999_100, 700_000_000),
+            (1_000_000_900, 300_000_000),
+        ] {
+            let exact = (base as f64 / 1e9).powf(exp as f64 / 1e9) * 1e9;
+            let down = pow(base, exp, Rounding::Down).unwrap();
+            let up = pow(base, exp, Rounding::Up).unwrap();
+            assert!(down as f64 <= exact && exact <= up as f64);
+            assert!(up - down <= 2);
+        }
+    }
+
     #[test]
     fn test_powers_for_swap() {
         for w_i in AVAILABLE_WEIGHTS.clone() {
@@ -221,7 +583,7 @@ mod tests {
         }
     }
 
-    fn check_epsilon(exact: u64, similar: u64) {
+    pub(crate) fn check_epsilon(exact: u64, similar: u64) {
         let diff = if exact > similar {
             exact - similar
         } else {
@@ -0,0 +1,52 @@
+use rust_decimal::Decimal;
+
+// Decimal carries at most 28 fractional digits
+pub const MAX_DECIMAL_SCALE: u32 = 28;
+
+/// token amount in base units as whole tokens, e.g. 1_500_000 with 6 decimals is 1.5. Digits past the 28th
+/// fractional one are truncated
+pub fn to_decimal(amount: u64, decimals: u8) -> Decimal {
+    let decimals = decimals as u32;
+    if decimals <= MAX_DECIMAL_SCALE {
+        return Decimal::from_i128_with_scale(amount as i128, decimals);
+    }
+
+    let truncated_amount = 10u64
+        .checked_pow(decimals - MAX_DECIMAL_SCALE)
+        .map_or(0, |divisor| amount / divisor);
+    Decimal::from_i128_with_scale(truncated_amount as i128, MAX_DECIMAL_SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_to_decimal() {
+        // 6 decimals, e.g. USDC
+        assert_eq!(to_decimal(1_500_000, 6), Decimal::from_str("1.5").unwrap());
+        assert_eq!(to_decimal(1, 6), Decimal::from_str("0.000001").unwrap());
+        assert_eq!(to_decimal(0, 6), Decimal::ZERO);
+        assert_eq!(to_decimal(1_500_000, 6).to_string(), "1.500000");
+
+        // 9 decimals, e.g. SOL
+        assert_eq!(to_decimal(1_500_000, 9), Decimal::from_str("0.0015").unwrap());
+        assert_eq!(to_decimal(2_000_000_000, 9), Decimal::TWO);
+        assert_eq!(
+            to_decimal(u64::MAX, 9),
+            Decimal::from_str("18446744073.709551615").unwrap()
+        );
+
+        assert_eq!(to_decimal(42, 0), Decimal::from(42));
+        assert_eq!(
+            to_decimal(u64::MAX, 28),
+            Decimal::from_str("0.0000000018446744073709551615").unwrap()
+        );
+        assert_eq!(
+            to_decimal(12_345, 30),
+            Decimal::from_str("0.0000000000000000000000000123").unwrap()
+        );
+        assert_eq!(to_decimal(u64::MAX, u8::MAX), Decimal::ZERO);
+    }
+}
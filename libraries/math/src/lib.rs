@@ -1,6 +1,7 @@
 pub mod base_pool_math;
 pub mod error;
 pub mod fixed_math;
+pub mod format;
 pub mod stable_math;
 pub mod swap_fee_math;
 pub mod weighted_math;
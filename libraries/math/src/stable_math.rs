@@ -1,10 +1,10 @@
 use crate::{
     error::StableMathError,
-    fixed_math::{self, FixedComplement, FixedDiv, FixedMul},
+    fixed_math::{self, FixedComplement, FixedDiv, FixedMul, ONE_U192},
 };
 use bn::{
-    safe_math::{CheckedDivCeil, CheckedMulDiv},
-    uint192, U192,
+    safe_math::{CheckedAddMul, CheckedDivCeil, CheckedMulDiv},
+    uint192, uint256, U192, U256,
 };
 
 pub const AMP_PRECISION: u64 = 1_000;
@@ -21,8 +21,13 @@ pub const MAX_SAFE_BALANCE: u64 = 3_000_000_000_000_000_000; // 3B
 pub const INV_THRESHOLD: u64 = 100;
 pub const BALANCE_THRESHOLD: u64 = 1;
 
+// Default iteration cap of the Newton-Raphson loops
+pub const MAX_ITERATIONS: usize = 255;
+
 pub const MIN_TOKENS: usize = 2;
-pub const MAX_TOKENS: usize = 5;
+// The invariant is close to the sum of balances and has to fit into u64, which limits the balances of larger
+// pools below MAX_SAFE_BALANCE (e.g. 8 tokens can hold about 2B each).
+pub const MAX_TOKENS: usize = 8;
 
 pub fn amp_precision_u192() -> U192 {
     uint192!(AMP_PRECISION)
@@ -33,6 +38,15 @@ pub fn amp_precision_u192() -> U192 {
 // The amplification parameter equals: A n^(n-1)
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-stable/contracts/StableMath.sol#L57-L120
 pub fn calc_invariant(amplification: u64, balances: &Vec<u64>) -> Result<u64, StableMathError> {
+    calc_invariant_with_max_iter(amplification, balances, MAX_ITERATIONS)
+}
+
+// Same as `calc_invariant`, but gives up with `InvariantDidntConverge` after `max_iter` iterations
+pub fn calc_invariant_with_max_iter(
+    amplification: u64,
+    balances: &Vec<u64>,
+    max_iter: usize,
+) -> Result<u64, StableMathError> {
     // invariant                                                                                 //
     // D = invariant                                                  D^(n+1)                    //
     // A = amplification coefficient      A  n^n S + D = A D n^n + -----------                   //
@@ -41,11 +55,18 @@ pub fn calc_invariant(amplification: u64, balances: &Vec<u64>) -> Result<u64, St
     // n = number of tokens                                                                      //
 
     // Always round down, to match Vyper's arithmetic (which always truncates).
-    let sum: u64 = balances.iter().sum(); // S in the Curve version
+    let sum = balances
+        .iter()
+        .try_fold(0u64, |sum, balance| sum.checked_add(*balance))
+        .ok_or(StableMathError::Overflow)?; // S in the Curve version
 
     if sum == 0 {
         return Ok(0);
     }
+    // The product of balances collapses to zero and is divided by, once any single balance is drained
+    if balances.contains(&0) {
+        return Err(StableMathError::ZeroBalance);
+    }
 
     let num_tokens = balances.len() as u64;
     let amp_times_total = amplification * num_tokens; // Ann in the Curve version
@@ -54,32 +75,32 @@ pub fn calc_invariant(amplification: u64, balances: &Vec<u64>) -> Result<u64, St
     let mut prev_invariant; // Dprev in the Curve version
     let mut invariant = sum; // D in the Curve version
 
-    for _ in 0..255 {
+    for _ in 0..max_iter {
         let mut p = invariant;
 
         for i in 0..balances.len() {
             // (p * invariant) / (balances[i] * num_tokens)
             p = p
-                .checked_mul_div_down(invariant, uint192!(balances[i] * num_tokens))
-                .unwrap();
+                .checked_mul_div_down(invariant, uint192!(balances[i]) * uint192!(num_tokens))
+                .ok_or(StableMathError::Overflow)?;
         }
 
         prev_invariant = invariant;
 
         invariant = (uint192!(amp_times_total)
             .checked_mul_div_down(sum, amp_precision_u192())
-            .unwrap()
+            .ok_or(StableMathError::Overflow)?
             + (p * uint192!(balances.len())))
         .checked_mul_div_down(
             invariant,
             uint192!(amp_times_total - AMP_PRECISION)
                 .checked_mul_div_down(invariant, amp_precision_u192())
-                .unwrap()
+                .ok_or(StableMathError::Overflow)?
                 + (uint192!(num_tokens.saturating_add(1)) * p),
         )
-        .unwrap();
+        .ok_or(StableMathError::Overflow)?;
 
-        let invariant = invariant.as_u64();
+        let invariant = u64::try_from(invariant).map_err(|_| StableMathError::Overflow)?;
         let prev_invariant = prev_invariant.as_u64();
 
         if invariant > prev_invariant {
@@ -94,6 +115,66 @@ pub fn calc_invariant(amplification: u64, balances: &Vec<u64>) -> Result<u64, St
     Err(StableMathError::InvariantDidntConverge)
 }
 
+// Same as `calc_invariant`, but keeps the running product of the balances in U256 without capping it at u128, so
+// pools with many large or very uneven balances only fail if the invariant itself doesn't fit u64
+pub fn calc_invariant_u256(amplification: u64, balances: &[u64]) -> Result<u64, StableMathError> {
+    let sum = balances
+        .iter()
+        .try_fold(0u64, |sum, balance| sum.checked_add(*balance))
+        .ok_or(StableMathError::Overflow)?;
+
+    if sum == 0 {
+        return Ok(0);
+    }
+    if balances.contains(&0) {
+        return Err(StableMathError::ZeroBalance);
+    }
+
+    let num_tokens = balances.len() as u64;
+    let amp_times_total = uint256!(amplification * num_tokens);
+    let amp_precision = uint256!(AMP_PRECISION);
+
+    // Growing the running product through the smallest balances first keeps the truncation of the divisions
+    // from wiping out its precision in uneven pools
+    let mut sorted_balances = balances.to_vec();
+    sorted_balances.sort_unstable();
+
+    let sum = uint256!(sum);
+    let mut invariant = sum;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut p = invariant;
+
+        for balance in &sorted_balances {
+            p = p.checked_mul(invariant).ok_or(StableMathError::Overflow)?
+                / (uint256!(*balance) * uint256!(num_tokens));
+        }
+
+        let prev_invariant = invariant;
+
+        let numerator = (amp_times_total * sum / amp_precision)
+            .checked_add_mul(p, uint256!(num_tokens))
+            .and_then(|x| x.checked_mul(invariant))
+            .ok_or(StableMathError::Overflow)?;
+        let denominator = ((amp_times_total - amp_precision) * invariant / amp_precision)
+            .checked_add_mul(p, uint256!(num_tokens + 1))
+            .ok_or(StableMathError::Overflow)?;
+        invariant = numerator / denominator;
+
+        if invariant > uint256!(u64::MAX) {
+            return Err(StableMathError::Overflow);
+        }
+        let invariant = invariant.as_u64();
+        let prev_invariant = prev_invariant.as_u64();
+
+        if invariant.abs_diff(prev_invariant) <= INV_THRESHOLD {
+            return Ok(invariant);
+        }
+    }
+
+    Err(StableMathError::InvariantDidntConverge)
+}
+
 // Computes how many tokens can be taken out of a pool if `token_amount_in` are sent, given the current balances.
 // The amplification parameter equals: A n^(n-1)
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-stable/contracts/StableMath.sol#L124-L159
@@ -121,7 +202,12 @@ pub fn calc_out_given_in(
     let mut new_balances = vec![];
     for i in 0..balances.len() {
         if i == token_index_in {
-            new_balances.push(balances[i] + token_amount_in);
+            // Beyond the safe balance the Newton-Raphson loops are no longer guaranteed to converge
+            let new_balance = balances[i]
+                .checked_add(token_amount_in)
+                .filter(|new_balance| *new_balance <= MAX_SAFE_BALANCE)
+                .ok_or(StableMathError::MaxSafeBalance)?;
+            new_balances.push(new_balance);
         } else {
             new_balances.push(balances[i]);
         }
@@ -134,7 +220,10 @@ pub fn calc_out_given_in(
         token_index_out,
     )?;
 
-    let token_amount_out = balances[token_index_out] - final_balance_out - 1;
+    // The final balance is rounded up, so for dust amounts in it can reach the current balance
+    let token_amount_out = balances[token_index_out]
+        .saturating_sub(final_balance_out)
+        .saturating_sub(1);
 
     Ok(token_amount_out)
 }
@@ -195,6 +284,12 @@ pub fn calc_pool_token_out_given_exact_tokens_in(
 ) -> Result<u64, StableMathError> {
     // LP out, so we round down overall.
 
+    // Scaling the supply by the invariant ratio can't mint the initial LP, which has to be seeded from
+    // calc_invariant instead
+    if pool_token_supply == 0 {
+        return Err(StableMathError::ZeroPoolTokenSupply);
+    }
+
     // First loop calculates the sum of all token balances, which will be used to calculate
     // the current weights of each token, relative to this sum
     let sum: u64 = balances.iter().sum();
@@ -279,6 +374,110 @@ pub fn calc_token_out_given_exact_pool_token_in(
     Ok(amount_out)
 }
 
+// See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-stable/contracts/StableMath.sol#L309-L350
+pub fn calc_token_in_given_exact_pool_token_out(
+    amplification: u64,
+    balances: &Vec<u64>,
+    token_index: usize,
+    amount_out: u64,
+    pool_token_supply: u64,
+    current_invariant: u64,
+    swap_fee: u64,
+) -> Result<u64, StableMathError> {
+    // Token in, so we round up overall.
+
+    // The invariant is scaled by the supply ratio, which an empty pool doesn't have
+    if pool_token_supply == 0 {
+        return Err(StableMathError::ZeroPoolTokenSupply);
+    }
+
+    let new_invariant = pool_token_supply
+        .checked_add(amount_out)
+        .ok_or(StableMathError::Overflow)?
+        .checked_mul_div_up(current_invariant, pool_token_supply)
+        .ok_or(StableMathError::Overflow)?;
+
+    // Calculate amount in without fee
+    let new_balance =
+        get_token_balance_given_invariant_n_all_other_balances(amplification, balances, new_invariant, token_index)?;
+    let amount_in_without_fee = new_balance
+        .checked_sub(balances[token_index])
+        .ok_or(StableMathError::Overflow)?;
+
+    // First calculate the sum of all token balances, which will be used to calculate
+    // the current weight of each token
+    let sum = balances
+        .iter()
+        .try_fold(0u64, |sum, balance| sum.checked_add(*balance))
+        .ok_or(StableMathError::Overflow)?;
+
+    // We can now compute how much extra balance is being deposited and used in virtual swaps, and charge swap fees
+    // accordingly.
+    let current_weight = balances[token_index].div_down(sum);
+    let taxable_percentage = current_weight.complement();
+    let taxable_amount = amount_in_without_fee.mul_up(taxable_percentage);
+    let non_taxable_amount = amount_in_without_fee - taxable_amount;
+
+    let amount_in = taxable_amount
+        .div_up(swap_fee.complement())
+        .checked_add(non_taxable_amount)
+        .ok_or(StableMathError::Overflow)?;
+
+    Ok(amount_in)
+}
+
+// Computes the spot price of token out in terms of token in, i.e. the marginal amount of token in paid per token
+// out at ONE scale, by differentiating the invariant equation at the current balances.
+// The amplification parameter equals: A n^(n-1)
+pub fn calc_spot_price(
+    amplification: u64,
+    balances: &Vec<u64>,
+    token_index_in: usize,
+    token_index_out: usize,
+    invariant: u64,
+) -> Result<u64, StableMathError> {
+    /**********************************************************************************************
+    // spotPrice                                                                                 //
+    // x = balance token in                            x * (A * n^n * y + K)                     //
+    // y = balance token out           spotPrice = -----------------------------                 //
+    // D = invariant                                   y * (A * n^n * x + K)                     //
+    // K = D^(n+1) / (n^n * P)                                                                   //
+     **********************************************************************************************/
+    if balances.contains(&0) {
+        return Err(StableMathError::ZeroBalance);
+    }
+
+    let num_tokens = balances.len() as u64;
+    let amp_times_total = uint192!(amplification * num_tokens);
+
+    let invariant = uint192!(invariant);
+    let mut p = invariant;
+    for balance in balances {
+        p = p
+            .checked_mul_div_down(invariant, uint192!(*balance) * uint192!(num_tokens))
+            .ok_or(StableMathError::Overflow)?;
+    }
+    // A * n^n carries AMP_PRECISION, so scale K along with it
+    let k = p.checked_mul(amp_precision_u192()).ok_or(StableMathError::Overflow)?;
+
+    let balance_in = uint192!(balances[token_index_in]);
+    let balance_out = uint192!(balances[token_index_out]);
+    let numerator = k
+        .checked_add_mul(amp_times_total, balance_out)
+        .and_then(|x| x.checked_mul(balance_in))
+        .ok_or(StableMathError::Overflow)?;
+    let denominator = k
+        .checked_add_mul(amp_times_total, balance_in)
+        .and_then(|x| x.checked_mul(balance_out))
+        .ok_or(StableMathError::Overflow)?;
+
+    let spot_price = numerator
+        .checked_mul_div_down(ONE_U192, denominator)
+        .ok_or(StableMathError::Overflow)?;
+
+    u64::try_from(spot_price).map_err(|_| StableMathError::Overflow)
+}
+
 // This function calculates the balance of a given token (token_index)
 // given all the other balances and the invariant
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-stable/contracts/StableMath.sol#L399-L449
@@ -287,20 +486,44 @@ fn get_token_balance_given_invariant_n_all_other_balances(
     balances: &Vec<u64>,
     invariant: u64,
     token_index: usize,
+) -> Result<u64, StableMathError> {
+    get_token_balance_given_invariant_n_all_other_balances_with_max_iter(
+        amplification,
+        balances,
+        invariant,
+        token_index,
+        MAX_ITERATIONS,
+    )
+}
+
+// Same as `get_token_balance_given_invariant_n_all_other_balances`, but gives up with `GetBalanceDidntConverge`
+// after `max_iter` iterations
+pub fn get_token_balance_given_invariant_n_all_other_balances_with_max_iter(
+    amplification: u64,
+    balances: &Vec<u64>,
+    invariant: u64,
+    token_index: usize,
+    max_iter: usize,
 ) -> Result<u64, StableMathError> {
     // Rounds result up overall
 
+    if balances.contains(&0) {
+        return Err(StableMathError::ZeroBalance);
+    }
+
     let num_tokens = balances.len() as u64;
     let amp_times_total = uint192!(amplification * num_tokens);
 
     let invariant = uint192!(invariant);
 
     let mut sum = balances[0];
-    let mut p = uint192!(balances[0] * num_tokens);
+    let mut p = uint192!(balances[0]) * uint192!(num_tokens);
     for i in 1..balances.len() {
-        let p_i = uint192!(balances[i] * num_tokens);
-        p = p.checked_mul_div_down(p_i, invariant).unwrap();
-        sum = sum + balances[i];
+        let p_i = uint192!(balances[i]) * uint192!(num_tokens);
+        p = p
+            .checked_mul_div_down(p_i, invariant)
+            .ok_or(StableMathError::Overflow)?;
+        sum = sum.checked_add(balances[i]).ok_or(StableMathError::Overflow)?;
     }
     // No need to use safe math, based on the loop above `sum` is greater than or equal to `balances[token_index]`
     sum = sum.saturating_sub(balances[token_index]);
@@ -310,20 +533,22 @@ fn get_token_balance_given_invariant_n_all_other_balances(
     // We remove the balance from c by multiplying it
     let c = invariant_2
         .checked_mul_div_up(amp_precision_u192(), amp_times_total * p)
-        .unwrap()
+        .ok_or(StableMathError::Overflow)?
         * uint192!(balances[token_index]);
     let b = invariant
         .checked_mul_div_down(amp_precision_u192(), amp_times_total)
-        .unwrap()
+        .ok_or(StableMathError::Overflow)?
         + sum;
 
     // We iterate to find the balance
     let mut prev_token_balance;
     // We multiply the first iteration outside the loop with the invariant to set the value of the
     // initial approximation.
-    let mut token_balance = (invariant_2 + c).checked_div_up(invariant + b).unwrap();
+    let mut token_balance = (invariant_2 + c)
+        .checked_div_up(invariant + b)
+        .ok_or(StableMathError::Overflow)?;
 
-    for _ in 0..255 {
+    for _ in 0..max_iter {
         prev_token_balance = token_balance;
 
         token_balance = (token_balance * token_balance + c)
@@ -331,10 +556,10 @@ fn get_token_balance_given_invariant_n_all_other_balances(
                 // No need to use checked arithmetic because max value of `token_balance` is u128::MAX
                 (token_balance << 1) + b - invariant, // token_balance * 2 + b - invariant
             )
-            .unwrap();
+            .ok_or(StableMathError::Overflow)?;
 
-        let token_balance = token_balance.as_u64();
-        let prev_token_balance = prev_token_balance.as_u64();
+        let token_balance = u64::try_from(token_balance).map_err(|_| StableMathError::Overflow)?;
+        let prev_token_balance = u64::try_from(prev_token_balance).map_err(|_| StableMathError::Overflow)?;
 
         if token_balance > prev_token_balance {
             if token_balance.saturating_sub(prev_token_balance) <= BALANCE_THRESHOLD {
@@ -351,6 +576,7 @@ fn get_token_balance_given_invariant_n_all_other_balances(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixed_math::ONE;
 
     #[test]
     fn test_calc_out_given_in() {
@@ -415,6 +641,397 @@ mod tests {
         assert_eq!(token_amount_out, 999845);
     }
 
+    #[test]
+    fn test_calc_in_given_out() {
+        let amplification = 5_000_000;
+        let balances = vec![40_000_000_000_000_000, 60_000_000_000_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        let token_amount_out = 100_000_000_000_000;
+        let token_a_in = calc_in_given_out(amplification, &balances, 0, 1, token_amount_out, invariant).unwrap();
+        let token_b_in = calc_in_given_out(amplification, &balances, 1, 0, token_amount_out, invariant).unwrap();
+        assert_eq!(token_a_in, 99991372350078);
+        assert_eq!(token_b_in, 100008729647366);
+
+        let amplification = 5_000_000;
+        let balances = vec![894_520_800_000_000, 467_581_800_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        // inverts the calc_out_given_in fixtures, rounding up on the smallest amount
+        let token_amount_out = 999_845_351_779;
+        let token_amount_in = calc_in_given_out(amplification, &balances, 0, 1, token_amount_out, invariant).unwrap();
+        assert_eq!(token_amount_in, 1000000000000);
+
+        let token_amount_in = calc_in_given_out(amplification, &balances, 0, 1, 999_845_869, invariant).unwrap();
+        assert_eq!(token_amount_in, 1000000000);
+
+        let token_amount_in = calc_in_given_out(amplification, &balances, 0, 1, 999_845, invariant).unwrap();
+        assert_eq!(token_amount_in, 1000001);
+
+        let amplification = 750_000;
+        let balances = vec![40_000_000_000_000_000, 50_000_000_000_000_000, 60_000_000_000_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+        let token_amount_in = calc_in_given_out(amplification, &balances, 2, 0, 1_000_000_000_000, invariant).unwrap();
+        assert_eq!(token_amount_in, 1000578025700);
+    }
+
+    #[test]
+    fn test_calc_in_given_out_round_trip() {
+        for amplification in [1_000, 100_000, 5_000_000] {
+            for balances in [
+                vec![894_520_800_000_000, 467_581_800_000_000],
+                vec![40_000_000_000_000_000, 60_000_000_000_000_000],
+                vec![1_000_000_000, 1_000_000_000],
+                vec![1_000_000_000_000, 1_500_000_000_000],
+            ] {
+                let invariant = calc_invariant(amplification, &balances).unwrap();
+                for (token_index_in, token_index_out) in [(0, 1), (1, 0)] {
+                    for amount_in in [1_000, 999_999, 1_000_000_000, 123_456_789_012, 10_000_000_000_000] {
+                        if amount_in > balances[token_index_in] / 10 {
+                            continue;
+                        }
+                        let amount_out = calc_out_given_in(
+                            amplification,
+                            &balances,
+                            token_index_in,
+                            token_index_out,
+                            amount_in,
+                            invariant,
+                        )
+                        .unwrap();
+                        let amount_in_again = calc_in_given_out(
+                            amplification,
+                            &balances,
+                            token_index_in,
+                            token_index_out,
+                            amount_out,
+                            invariant,
+                        )
+                        .unwrap();
+                        assert!(amount_in_again.abs_diff(amount_in) <= 1);
+                    }
+
+                    // dust amounts in round down rather than underflowing, and never cost more to buy back
+                    for amount_in in [1, 7] {
+                        let amount_out = calc_out_given_in(
+                            amplification,
+                            &balances,
+                            token_index_in,
+                            token_index_out,
+                            amount_in,
+                            invariant,
+                        )
+                        .unwrap();
+                        let amount_in_again = calc_in_given_out(
+                            amplification,
+                            &balances,
+                            token_index_in,
+                            token_index_out,
+                            amount_out,
+                            invariant,
+                        )
+                        .unwrap();
+                        assert!(amount_in_again <= amount_in + 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_calc_out_given_in_many_tokens() {
+        for num_tokens in 6..=MAX_TOKENS as u64 {
+            // Leave room for the amount in, the balances still have to sum up below u64::MAX
+            let max_balance = u64::MAX / (num_tokens + 1);
+            for amplification in [1_000, 100_000, 5_000_000, MAX_AMP as u64 * AMP_PRECISION] {
+                for balances in [
+                    vec![max_balance; num_tokens as usize],
+                    (1..=num_tokens).map(|i| max_balance / i).collect(),
+                    (0..num_tokens).map(|i| 1_000_000_000_000 * (i + 1)).collect(),
+                ] {
+                    let invariant = calc_invariant(amplification, &balances).unwrap();
+                    let sum: u64 = balances.iter().sum();
+                    assert!(invariant <= sum);
+
+                    let token_amount_in = balances[0] / 100;
+                    let token_amount_out =
+                        calc_out_given_in(amplification, &balances, 0, 1, token_amount_in, invariant).unwrap();
+                    assert!(token_amount_out > 0);
+
+                    // Swapping back converges to the amount in, up to the rounding of both directions
+                    let token_amount_in_back =
+                        calc_in_given_out(amplification, &balances, 0, 1, token_amount_out, invariant).unwrap();
+                    assert!(token_amount_in_back.abs_diff(token_amount_in) <= 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_iter() {
+        let amplification = 100_000;
+        let balances = vec![1_000_000_000_000, 1_200_000_000_000, 900_000_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+        assert_eq!(calc_invariant_with_max_iter(amplification, &balances, 9), Ok(invariant));
+        assert_eq!(
+            calc_invariant_with_max_iter(amplification, &balances, 1),
+            Err(StableMathError::InvariantDidntConverge)
+        );
+        assert_eq!(
+            calc_invariant_with_max_iter(amplification, &balances, 0),
+            Err(StableMathError::InvariantDidntConverge)
+        );
+
+        let balance =
+            get_token_balance_given_invariant_n_all_other_balances(amplification, &balances, invariant, 1).unwrap();
+        assert_eq!(
+            get_token_balance_given_invariant_n_all_other_balances_with_max_iter(
+                amplification,
+                &balances,
+                invariant,
+                1,
+                9
+            ),
+            Ok(balance)
+        );
+        assert_eq!(
+            get_token_balance_given_invariant_n_all_other_balances_with_max_iter(
+                amplification,
+                &balances,
+                invariant,
+                1,
+                0
+            ),
+            Err(StableMathError::GetBalanceDidntConverge)
+        );
+    }
+
+    #[test]
+    fn test_calc_spot_price() {
+        let amplification = 100_000;
+        let balances = vec![1_000_000_000_000, 1_000_000_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+        assert_eq!(calc_spot_price(amplification, &balances, 0, 1, invariant), Ok(ONE));
+
+        // the scarcer token out costs more of token in, and the price flips the other way
+        let balances = vec![1_500_000_000_000, 500_000_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+        let spot_price = calc_spot_price(amplification, &balances, 0, 1, invariant).unwrap();
+        let inverse_spot_price = calc_spot_price(amplification, &balances, 1, 0, invariant).unwrap();
+        assert_eq!(spot_price, 1017535241);
+        assert_eq!(inverse_spot_price, 982766944);
+        assert!(spot_price > ONE);
+        assert!(spot_price.mul_down(inverse_spot_price).abs_diff(ONE) <= 1);
+
+        // a tiny swap trades at the spot price
+        let amount_in = 1_000_000;
+        let amount_out = calc_out_given_in(amplification, &balances, 0, 1, amount_in, invariant).unwrap();
+        assert!(amount_out.mul_down(spot_price).abs_diff(amount_in) <= 3);
+
+        assert_eq!(
+            calc_spot_price(amplification, &vec![1_000_000_000_000, 0], 0, 1, invariant),
+            Err(StableMathError::ZeroBalance)
+        );
+    }
+
+    #[test]
+    fn test_calc_invariant_u256() {
+        let amplification = 100_000;
+        let balances = [1_000_000_000_000_000_000; 5];
+        assert_eq!(
+            calc_invariant_u256(amplification, &balances),
+            calc_invariant(amplification, &balances.to_vec())
+        );
+
+        // the running product of the two small balances doesn't fit u128
+        let balances = [
+            3_000_000_000_000_000_000,
+            3_000_000_000_000_000_000,
+            3_000_000_000_000_000_000,
+            1_000_000,
+            1_000_000,
+        ];
+        assert_eq!(
+            calc_invariant(amplification, &balances.to_vec()),
+            Err(StableMathError::Overflow)
+        );
+        // the exact root of the invariant equation
+        assert_eq!(calc_invariant_u256(amplification, &balances), Ok(2_690_808_448_080_286));
+
+        assert_eq!(calc_invariant_u256(amplification, &[0; 5]), Ok(0));
+        assert_eq!(
+            calc_invariant_u256(amplification, &[1_000_000, 0]),
+            Err(StableMathError::ZeroBalance)
+        );
+        assert_eq!(
+            calc_invariant_u256(amplification, &[u64::MAX, 1]),
+            Err(StableMathError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_max_safe_balance() {
+        let amplification = 100_000;
+        let balances = vec![MAX_SAFE_BALANCE - 1_000_000_000, MAX_SAFE_BALANCE];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        assert!(calc_out_given_in(amplification, &balances, 0, 1, 1_000_000_000, invariant).is_ok());
+        assert_eq!(
+            calc_out_given_in(amplification, &balances, 0, 1, 1_000_000_001, invariant),
+            Err(StableMathError::MaxSafeBalance)
+        );
+        assert_eq!(
+            calc_out_given_in(amplification, &balances, 0, 1, u64::MAX, invariant),
+            Err(StableMathError::MaxSafeBalance)
+        );
+    }
+
+    #[test]
+    fn test_zero_balance() {
+        let amplification = 100_000;
+        assert_eq!(calc_invariant(amplification, &vec![0, 0]), Ok(0));
+        assert_eq!(
+            calc_invariant(amplification, &vec![1_000_000_000, 0]),
+            Err(StableMathError::ZeroBalance)
+        );
+
+        let balances = vec![1_000_000_000, 0, 1_000_000_000];
+        assert_eq!(
+            calc_out_given_in(amplification, &balances, 0, 1, 1_000_000, 2_000_000_000),
+            Err(StableMathError::ZeroBalance)
+        );
+        assert_eq!(
+            calc_out_given_in(amplification, &balances, 0, 2, 1_000_000, 2_000_000_000),
+            Err(StableMathError::ZeroBalance)
+        );
+        assert_eq!(
+            calc_in_given_out(amplification, &balances, 0, 2, 1_000_000, 2_000_000_000),
+            Err(StableMathError::ZeroBalance)
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let balances = vec![u64::MAX / 2, u64::MAX / 2, 2];
+        assert_eq!(calc_invariant(5_000_000, &balances), Err(StableMathError::Overflow));
+        assert_eq!(
+            get_token_balance_given_invariant_n_all_other_balances(5_000_000, &balances, u64::MAX, 0),
+            Err(StableMathError::Overflow)
+        );
+
+        let balances = vec![u64::MAX / 2, u64::MAX / 2];
+        assert!(calc_invariant(5_000_000, &balances).is_ok());
+    }
+
+    #[test]
+    fn test_calc_token_in_given_exact_pool_token_out() {
+        let amplification = 5_000_000;
+        let balances = vec![894_520_800_000_000, 467_581_800_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        for token_index in 0..balances.len() {
+            for amount_out in [1_000_000_000, 1_000_000_000_000, 100_000_000_000_000] {
+                // Without fees, burning the minted amount from the grown pool gives back the amount in
+                let amount_in = calc_token_in_given_exact_pool_token_out(
+                    amplification,
+                    &balances,
+                    token_index,
+                    amount_out,
+                    invariant,
+                    invariant,
+                    0,
+                )
+                .unwrap();
+
+                let mut new_balances = balances.clone();
+                new_balances[token_index] += amount_in;
+                let new_invariant = calc_invariant(amplification, &new_balances).unwrap();
+                let amount_in_back = calc_token_out_given_exact_pool_token_in(
+                    amplification,
+                    &new_balances,
+                    token_index,
+                    amount_out,
+                    invariant + amount_out,
+                    new_invariant,
+                    0,
+                )
+                .unwrap();
+                assert!(amount_in_back <= amount_in);
+                assert!(amount_in - amount_in_back <= 3);
+
+                let amount_in_with_fee = calc_token_in_given_exact_pool_token_out(
+                    amplification,
+                    &balances,
+                    token_index,
+                    amount_out,
+                    invariant,
+                    invariant,
+                    100_000,
+                )
+                .unwrap();
+                assert!(amount_in_with_fee > amount_in);
+            }
+        }
+
+        assert_eq!(
+            calc_token_in_given_exact_pool_token_out(amplification, &balances, 0, u64::MAX, invariant, invariant, 0),
+            Err(StableMathError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_calc_token_in_given_exact_pool_token_out_errors() {
+        let amplification = 5_000_000;
+        let balances = vec![894_520_800_000_000, 467_581_800_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        assert_eq!(
+            calc_token_in_given_exact_pool_token_out(amplification, &balances, 0, 1_000_000_000, 0, invariant, 0),
+            Err(StableMathError::ZeroPoolTokenSupply)
+        );
+
+        // an invariant below the balances solves for a new balance under the current one
+        assert_eq!(
+            calc_token_in_given_exact_pool_token_out(amplification, &vec![ONE, ONE], 0, 1, 1, 1, 0),
+            Err(StableMathError::Overflow)
+        );
+
+        // the fee on top of the amount in no longer fits u64
+        let balances = vec![1 << 60, 1 << 60];
+        assert_eq!(
+            calc_token_in_given_exact_pool_token_out(
+                amplification,
+                &balances,
+                0,
+                1,
+                u64::MAX - 1,
+                u64::MAX - 1,
+                500_000_000
+            ),
+            Err(StableMathError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_calc_pool_token_out_given_exact_tokens_in_zero_supply() {
+        let amplification = 5_000_000;
+        let balances = vec![894_520_800_000_000, 467_581_800_000_000];
+        let invariant = calc_invariant(amplification, &balances).unwrap();
+
+        // the initial deposit is seeded from the invariant, not scaled from the supply
+        assert_eq!(
+            calc_pool_token_out_given_exact_tokens_in(
+                amplification,
+                &balances,
+                &vec![1_000_000_000_000, 1_000_000_000_000],
+                0,
+                invariant,
+                100_000,
+            ),
+            Err(StableMathError::ZeroPoolTokenSupply)
+        );
+    }
+
     #[test]
     fn test_calc_pool_token_out_given_exact_tokens_in() {
         let amplification = 5_000_000;
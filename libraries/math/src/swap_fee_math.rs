@@ -1,50 +1,33 @@
 use crate::fixed_math::FixedMul;
 
+// (x_amount upper bound, swap fee multiplier), the swap fee is fully discounted beyond the last bound
+pub const SWAP_FEE_DISCOUNT_TIERS: [(u64, u64); 10] = [
+    (100_000_000_000_000, 1_000_000_000),  // No discount
+    (200_000_000_000_000, 900_000_000),    // 10% discount
+    (400_000_000_000_000, 800_000_000),    // 20% discount
+    (800_000_000_000_000, 700_000_000),    // 30% discount
+    (1_600_000_000_000_000, 600_000_000),  // 40% discount
+    (3_200_000_000_000_000, 500_000_000),  // 50% discount
+    (6_400_000_000_000_000, 400_000_000),  // 60% discount
+    (12_800_000_000_000_000, 300_000_000), // 70% discount
+    (25_600_000_000_000_000, 200_000_000), // 80% discount
+    (51_200_000_000_000_000, 100_000_000), // 90% discount
+];
+
 pub fn calc_swap_fee_in_discount(swap_fee: u64, x_amount: u64) -> u64 {
-    // No discount
-    if x_amount < 100_000_000_000_000 {
-        swap_fee
-    }
-    // 10% discount
-    else if x_amount < 200_000_000_000_000 {
-        swap_fee.mul_up(900_000_000)
-    }
-    // 20% discount
-    else if x_amount < 400_000_000_000_000 {
-        swap_fee.mul_up(800_000_000)
-    }
-    // 30% discount
-    else if x_amount < 800_000_000_000_000 {
-        swap_fee.mul_up(700_000_000)
-    }
-    // 40% discount
-    else if x_amount < 1_600_000_000_000_000 {
-        swap_fee.mul_up(600_000_000)
-    }
-    // 50% discount
-    else if x_amount < 3_200_000_000_000_000 {
-        swap_fee.mul_up(500_000_000)
-    }
-    // 60% discount
-    else if x_amount < 6_400_000_000_000_000 {
-        swap_fee.mul_up(400_000_000)
-    }
-    // 70% discount
-    else if x_amount < 12_800_000_000_000_000 {
-        swap_fee.mul_up(300_000_000)
-    }
-    // 80% discount
-    else if x_amount < 25_600_000_000_000_000 {
-        swap_fee.mul_up(200_000_000)
-    }
-    // 90% discount
-    else if x_amount < 51_200_000_000_000_000 {
-        swap_fee.mul_up(100_000_000)
+    calc_swap_fee_in_discount_with_tiers(swap_fee, x_amount, &SWAP_FEE_DISCOUNT_TIERS)
+}
+
+/// `tiers` must be sorted by their upper bound
+pub fn calc_swap_fee_in_discount_with_tiers(swap_fee: u64, x_amount: u64, tiers: &[(u64, u64)]) -> u64 {
+    for (threshold, multiplier) in tiers {
+        if x_amount < *threshold {
+            return swap_fee.mul_up(*multiplier);
+        }
     }
+
     // 100% discount
-    else {
-        0
-    }
+    0
 }
 
 #[cfg(test)]
@@ -108,4 +91,15 @@ mod tests {
         let swap_fee = calc_swap_fee_in_discount(SWAP_FEE, 51_200_000_000_000_001);
         assert_eq!(swap_fee, 0);
     }
+
+    #[test]
+    fn test_swap_fee_in_discount_with_tiers() {
+        let tiers = [(1_000, 1_000_000_000), (2_000, 750_000_000)];
+        assert_eq!(calc_swap_fee_in_discount_with_tiers(SWAP_FEE, 999, &tiers), SWAP_FEE);
+        assert_eq!(calc_swap_fee_in_discount_with_tiers(SWAP_FEE, 1_000, &tiers), 7_500);
+        assert_eq!(calc_swap_fee_in_discount_with_tiers(SWAP_FEE, 1_999, &tiers), 7_500);
+        assert_eq!(calc_swap_fee_in_discount_with_tiers(SWAP_FEE, 2_000, &tiers), 0);
+
+        assert_eq!(calc_swap_fee_in_discount_with_tiers(SWAP_FEE, 0, &[]), 0);
+    }
 }
@@ -4,12 +4,16 @@ use crate::fixed_math::FixedComplement;
 use crate::fixed_math::FixedDiv;
 use crate::fixed_math::FixedMul;
 use crate::fixed_math::FixedPow;
+use bn::safe_math::{CheckedMulDiv, Rounding};
 
 // A minimum normalized weight imposes a maximum weight ratio. We need this due to limitations in the
 // implementation of the power function, as these ratios are often exponents.
 pub const MIN_WEIGHT: u64 = 100_000_000; // 10%
 pub const MAX_WEIGHT: u64 = 900_000_000; // 90%
 
+// Normalized weights may sum to ONE give or take this much.
+pub const WEIGHT_SUM_TOLERANCE: u64 = 1_000; // 0.0001%
+
 pub const MIN_SWAP_FEE: u64 = 100_000; // 0.01%
 pub const MAX_SWAP_FEE: u64 = 25_000_000; // 2.5%
 
@@ -17,7 +21,9 @@ pub const MAX_SWAP_FEE: u64 = 25_000_000; // 2.5%
 pub const MAX_SAFE_BALANCE: u64 = 4_000_000_000_000_000_000; // 4B
 
 pub const MIN_TOKENS: usize = 2;
-pub const MAX_TOKENS: usize = 4;
+// Each token costs the invariant up to ~1e-9 at MAX_SAFE_BALANCE, always rounding down, so with 8 tokens it
+// stays well within 1e-7 of the exact value.
+pub const MAX_TOKENS: usize = 8;
 
 // Pool limits that arise from limitations in the fixed point power function (and the imposed 1:100 maximum weight ratio).
 
@@ -55,6 +61,71 @@ pub fn calc_invariant(balances: &Vec<u64>, normalized_weights: &Vec<u64>) -> Res
     }
 }
 
+// Same as `calc_invariant`, but tells a degenerate pool (`ZeroInvariant`) apart from one whose balances or
+// weights overflow the fixed point math (`Overflow`) instead of panicking on the latter. Mismatched lengths
+// are `InvalidWeights`.
+pub fn calc_invariant_checked(balances: &[u64], normalized_weights: &[u64]) -> Result<u64, WeightedMathError> {
+    if balances.len() != normalized_weights.len() {
+        return Err(WeightedMathError::InvalidWeights);
+    }
+
+    // the lengths match, so the geometric mean only fails on overflow
+    let invariant = geometric_mean(balances, normalized_weights).ok_or(WeightedMathError::Overflow)?;
+
+    if invariant > 0 {
+        Ok(invariant)
+    } else {
+        Err(WeightedMathError::ZeroInvariant)
+    }
+}
+
+// Weighted geometric mean of the balances, the same product as the invariant but returning `None` instead of
+// panicking if the lengths mismatch or anything overflows. Rounds down like the invariant.
+pub fn geometric_mean(balances: &[u64], weights: &[u64]) -> Option<u64> {
+    if balances.len() != weights.len() {
+        return None;
+    }
+
+    balances
+        .iter()
+        .zip(weights)
+        .try_fold(fixed_math::ONE, |product, (balance, weight)| {
+            let power = fixed_math::pow(*balance, *weight, Rounding::Down)?;
+            product.checked_mul_div_down(power, fixed_math::ONE)
+        })
+}
+
+// Pool tokens to mint to the protocol for its cut of the swap fees collected between two invariant snapshots
+// taken at the same supply, following Balancer's `_calcDueProtocolSwapFeeBptAmount`. Swap fees are the only
+// thing that grows the invariant at a fixed supply, so (growth - 1) / growth of the pool belongs to them and the
+// protocol owns `protocol_fee_pct` of that. The amount minted dilutes the current supply to exactly that
+// ownership. Rounds down, returns `0` if the invariant didn't grow and `None` for a zero previous invariant, a
+// protocol fee over ONE or overflow.
+pub fn calc_due_protocol_fees(
+    previous_invariant: u64,
+    current_invariant: u64,
+    current_supply: u64,
+    protocol_fee_pct: u64,
+) -> Option<u64> {
+    if previous_invariant == 0 || protocol_fee_pct > fixed_math::ONE {
+        return None;
+    }
+
+    let growth = current_invariant.checked_mul_div_down(fixed_math::ONE, previous_invariant)?;
+    if growth <= fixed_math::ONE {
+        return Some(0);
+    }
+
+    let swap_fees_pct = (growth - fixed_math::ONE).checked_mul_div_down(fixed_math::ONE, growth)?;
+    let protocol_ownership_pct = swap_fees_pct.checked_mul_div_down(protocol_fee_pct, fixed_math::ONE)?;
+
+    // bpt / (supply + bpt) = ownership, solved for bpt. The ownership stays below ONE since the swap fees
+    // percentage does
+    current_supply
+        .checked_mul_div_down(protocol_ownership_pct, fixed_math::ONE)?
+        .checked_mul_div_down(fixed_math::ONE, protocol_ownership_pct.complement())
+}
+
 // Computes how many tokens can be taken out of a pool if `amountIn` are sent, given the
 // current balances and weights.
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-weighted/contracts/WeightedMath.sol#L78-L109
@@ -141,8 +212,17 @@ pub fn calc_pool_token_out_given_exact_token_in(
 ) -> Result<u64, WeightedMathError> {
     // LP out, so we round down overall.
 
+    // Scaling the supply by the invariant ratio can't mint the initial LP, which has to be seeded from
+    // calc_invariant instead
+    if pool_token_supply == 0 {
+        return Err(WeightedMathError::ZeroPoolTokenSupply);
+    }
+
     let balance_ratio_with_fee = (balance + amount_in).div_down(balance);
-    let invariant_ratio_with_fees = balance_ratio_with_fee.mul_down(normalized_weight) + normalized_weight.complement();
+    let invariant_ratio_with_fees = balance_ratio_with_fee.mul_down(normalized_weight)
+        + normalized_weight
+            .checked_complement()
+            .ok_or(WeightedMathError::InvalidWeights)?;
 
     let amount_in_without_fee = if balance_ratio_with_fee > invariant_ratio_with_fees {
         let non_taxable_amount = if invariant_ratio_with_fees > fixed_math::ONE {
@@ -180,6 +260,12 @@ pub fn calc_pool_token_out_given_exact_tokens_in(
     pool_token_supply: u64,
     swap_fee: u64,
 ) -> Result<u64, WeightedMathError> {
+    // Scaling the supply by the invariant ratio can't mint the initial LP, which has to be seeded from
+    // calc_invariant instead
+    if pool_token_supply == 0 {
+        return Err(WeightedMathError::ZeroPoolTokenSupply);
+    }
+
     let mut balance_ratios_with_fee = vec![];
     let mut invariant_ratio_with_fees = 0;
 
@@ -228,6 +314,81 @@ pub fn calc_pool_token_out_given_exact_tokens_in(
     }
 }
 
+// See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-weighted/contracts/WeightedMath.sol#L357-L421
+pub fn calc_pool_token_in_given_exact_tokens_out(
+    balances: &[u64],
+    normalized_weights: &[u64],
+    amounts_out: &[u64],
+    pool_token_supply: u64,
+    swap_fee: u64,
+) -> Result<u64, WeightedMathError> {
+    // LP in, so we round up overall.
+
+    if normalized_weights.len() != balances.len() {
+        return Err(WeightedMathError::InvalidWeights);
+    }
+    if amounts_out.len() != balances.len() {
+        return Err(WeightedMathError::InvalidAmounts);
+    }
+
+    let mut balance_ratios_without_fee = vec![];
+    let mut invariant_ratio_without_fees = 0;
+
+    for i in 0..balances.len() {
+        let balance_ratio_without_fee = balances[i]
+            .checked_sub(amounts_out[i])
+            .ok_or(WeightedMathError::MinInvariantRatio)?
+            .div_up(balances[i]);
+        balance_ratios_without_fee.push(balance_ratio_without_fee);
+        invariant_ratio_without_fees += balance_ratio_without_fee.mul_up(normalized_weights[i]);
+    }
+
+    let mut invariant_ratio = fixed_math::ONE;
+    for i in 0..balances.len() {
+        let amount_out_with_fee;
+
+        // Swap fees are typically charged on 'token in', but there is no 'token in' here, so we apply it to
+        // 'token out'. This results in slightly larger price impact.
+        if invariant_ratio_without_fees > balance_ratios_without_fee[i] {
+            let non_taxable_amount = balances[i].mul_down(invariant_ratio_without_fees.complement());
+            let taxable_amount = amounts_out[i]
+                .checked_sub(non_taxable_amount)
+                .ok_or(WeightedMathError::Overflow)?;
+            let taxable_amount_plus_fees = taxable_amount.div_up(swap_fee.complement());
+            amount_out_with_fee = non_taxable_amount + taxable_amount_plus_fees;
+        } else {
+            amount_out_with_fee = amounts_out[i];
+
+            // If a token's amount out is not being charged a swap fee then it might be zero (e.g. when exiting a
+            // Pool with only a subset of tokens). In this case, `balance_ratio` will equal `FixedPoint.ONE`, and
+            // the `invariantRatio` will not change at all. We therefore skip to the next iteration, avoiding
+            // the costly `powDown` call.
+            if amount_out_with_fee == 0 {
+                continue;
+            }
+        }
+
+        let balance_ratio = balances[i]
+            .checked_sub(amount_out_with_fee)
+            .ok_or(WeightedMathError::MinInvariantRatio)?
+            .div_down(balances[i]);
+        invariant_ratio = invariant_ratio.mul_down(balance_ratio.pow_down(normalized_weights[i]));
+    }
+
+    if invariant_ratio < MIN_INVARIANT_RATIO {
+        return Err(WeightedMathError::MinInvariantRatio);
+    }
+
+    // Every balance ratio is at most ONE and rounds down, so the invariant can only shrink here
+    let amount_in = pool_token_supply.mul_up(
+        invariant_ratio
+            .checked_complement()
+            .ok_or(WeightedMathError::MaxInvariantRatio)?,
+    );
+
+    Ok(amount_in)
+}
+
 // See: https://github.com/stabbleorg/balancer-v2-monorepo/blob/master/pkg/pool-weighted/contracts/WeightedMath.sol#L423-L462
 pub fn calc_token_out_given_exact_pool_token_in(
     balance: u64,
@@ -265,7 +426,11 @@ pub fn calc_token_out_given_exact_pool_token_in(
 
     // Swap fees are typically charged on 'token in', but there is no 'token in' here, so we apply it
     // to 'token out'. This results in slightly larger price impact. Fees are rounded up.
-    let taxable_amount = amount_out_without_fee.mul_up(normalized_weight.complement());
+    let taxable_amount = amount_out_without_fee.mul_up(
+        normalized_weight
+            .checked_complement()
+            .ok_or(WeightedMathError::InvalidWeights)?,
+    );
     let non_taxable_amount = amount_out_without_fee - taxable_amount;
     let taxable_amount_minus_fees = taxable_amount.mul_down(swap_fee.complement());
 
@@ -275,6 +440,65 @@ pub fn calc_token_out_given_exact_pool_token_in(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixed_math::tests::check_epsilon;
+
+    const MANY_TOKEN_WEIGHTS: [&[u64]; 7] = [
+        &[100_000_000, 150_000_000, 200_000_000, 250_000_000, 300_000_000],
+        &[200_000_000; 5],
+        &[
+            100_000_000,
+            100_000_000,
+            150_000_000,
+            150_000_000,
+            200_000_000,
+            300_000_000,
+        ],
+        &[
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            200_000_000,
+            300_000_000,
+        ],
+        &[125_000_000; 8],
+        // the weights that lose the most in the power function
+        &[
+            104_000_000,
+            104_000_000,
+            114_000_000,
+            114_000_000,
+            134_000_000,
+            134_000_000,
+            148_000_000,
+            148_000_000,
+        ],
+        &[
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            100_000_000,
+            300_000_000,
+        ],
+    ];
+
+    fn exact_invariant(balances: &[u64], normalized_weights: &[u64]) -> u64 {
+        let invariant = balances
+            .iter()
+            .zip(normalized_weights)
+            .map(|(balance, weight)| (*balance as f64 / 1e9).powf(*weight as f64 / 1e9))
+            .product::<f64>();
+        (invariant * 1e9) as u64
+    }
+
+    fn check_invariant_epsilon(exact: u64, invariant: u64) {
+        check_epsilon(exact, invariant);
+        assert!(invariant <= exact);
+    }
 
     #[test]
     fn test_calc_invariant() {
@@ -283,7 +507,7 @@ mod tests {
             &vec![500_000_000, 500_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 1999999999999899652);
+        assert_eq!(invariant, 1999999999999931274);
 
         let invariant = calc_invariant(
             &vec![
@@ -295,7 +519,7 @@ mod tests {
             &vec![100_000_000, 200_000_000, 300_000_000, 400_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 3999999829243548079);
+        assert_eq!(invariant, 3999999999413330870);
 
         let invariant = calc_invariant(
             &vec![
@@ -306,7 +530,7 @@ mod tests {
             &vec![330_000_000, 330_000_000, 340_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 3999999845679133687);
+        assert_eq!(invariant, 3999999999693428519);
 
         let invariant = calc_invariant(
             &vec![
@@ -317,21 +541,65 @@ mod tests {
             &vec![200_000_000, 200_000_000, 600_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 3999999833239242752);
+        assert_eq!(invariant, 3999999999670278039);
 
         let invariant = calc_invariant(
             &vec![4_000_000_000_000_000_000, 4_000_000_000_000_000_000],
             &vec![100_000_000, 900_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 3999999913148972546);
+        assert_eq!(invariant, 3999999999653243286);
 
         let invariant = calc_invariant(
             &vec![4_000_000_000_000_000_000, 4_000_000_000_000_000_000],
             &vec![200_000_000, 800_000_000],
         )
         .unwrap();
-        assert_eq!(invariant, 3999999916139535002);
+        assert_eq!(invariant, 3999999999762167985);
+    }
+
+    #[test]
+    fn test_calc_invariant_many_tokens() {
+        for normalized_weights in MANY_TOKEN_WEIGHTS {
+            assert_eq!(normalized_weights.iter().sum::<u64>(), fixed_math::ONE);
+
+            let balances = vec![MAX_SAFE_BALANCE; normalized_weights.len()];
+            let invariant = calc_invariant(&balances, &normalized_weights.to_vec()).unwrap();
+            check_invariant_epsilon(exact_invariant(&balances, normalized_weights), invariant);
+
+            let balances: Vec<u64> = (0..normalized_weights.len() as u64)
+                .map(|i| MAX_SAFE_BALANCE / (i * 7 + 1))
+                .collect();
+            let invariant = calc_invariant(&balances, &normalized_weights.to_vec()).unwrap();
+            check_invariant_epsilon(exact_invariant(&balances, normalized_weights), invariant);
+        }
+    }
+
+    #[test]
+    fn test_calc_out_given_in_many_tokens() {
+        for normalized_weights in MANY_TOKEN_WEIGHTS {
+            for (i, weight_in) in normalized_weights.iter().enumerate() {
+                for (o, weight_out) in normalized_weights.iter().enumerate() {
+                    if i == o {
+                        continue;
+                    }
+
+                    let balance_in = MAX_SAFE_BALANCE / (i as u64 + 1);
+                    let balance_out = MAX_SAFE_BALANCE / (o as u64 + 1);
+                    let amount_in = balance_in.mul_down(MAX_IN_RATIO) / 3;
+                    let amount_out =
+                        calc_out_given_in(balance_in, *weight_in, balance_out, *weight_out, amount_in).unwrap();
+
+                    let base = balance_in as f64 / (balance_in + amount_in) as f64;
+                    let exact = balance_out as f64 * (1.0 - base.powf(*weight_in as f64 / *weight_out as f64));
+                    // Only the two tokens are involved, so the error doesn't grow with the token count and is
+                    // dominated by rounding the weight ratio
+                    let exact = exact as u64;
+                    assert!(amount_out <= exact);
+                    assert!((exact - amount_out).div_up(exact) < 1_000); // 0.0001%
+                }
+            }
+        }
     }
 
     #[test]
@@ -364,7 +632,7 @@ mod tests {
             100_000_000_000,
         )
         .unwrap();
-        assert_eq!(amount_out, 396983388);
+        assert_eq!(amount_out, 387103711);
 
         let amount_out = calc_out_given_in(
             366851436508161000,
@@ -377,6 +645,25 @@ mod tests {
         assert_eq!(amount_out, 390121823);
     }
 
+    #[test]
+    fn test_calc_pool_token_out_zero_supply() {
+        // the initial deposit is seeded from the invariant, not scaled from the supply
+        assert_eq!(
+            calc_pool_token_out_given_exact_token_in(1_000_000_000, 500_000_000, 1_000_000_000, 0, 10_000_000),
+            Err(WeightedMathError::ZeroPoolTokenSupply)
+        );
+        assert_eq!(
+            calc_pool_token_out_given_exact_tokens_in(
+                &vec![1_000_000_000, 1_000_000_000],
+                &vec![500_000_000, 500_000_000],
+                &vec![1_000_000_000, 1_000_000_000],
+                0,
+                10_000_000,
+            ),
+            Err(WeightedMathError::ZeroPoolTokenSupply)
+        );
+    }
+
     #[test]
     fn test_calc_pool_token_out() {
         let amount_out = calc_pool_token_out_given_exact_token_in(
@@ -427,7 +714,7 @@ mod tests {
             10_000_000,
         )
         .unwrap();
-        assert_eq!(amount_out, 2236003831023460);
+        assert_eq!(amount_out, 2236012775110337);
     }
 
     #[test]
@@ -452,4 +739,244 @@ mod tests {
         .unwrap();
         assert_eq!(amount_out, 986045000000);
     }
+
+    #[test]
+    fn test_calc_pool_token_in_given_exact_tokens_out_invalid_lengths() {
+        let balances = [5_000_000_000_000_000_000, 1_000_000_000_000_000_000];
+        let normalized_weights = [500_000_000, 500_000_000];
+        let pool_token_supply = 2236021719197214567 << 1;
+
+        for amounts_out in [
+            &[5_000_000_000_000_000][..],
+            &[5_000_000_000_000_000, 0, 0][..],
+            &[][..],
+        ] {
+            assert_eq!(
+                calc_pool_token_in_given_exact_tokens_out(
+                    &balances,
+                    &normalized_weights,
+                    amounts_out,
+                    pool_token_supply,
+                    10_000_000
+                ),
+                Err(WeightedMathError::InvalidAmounts)
+            );
+        }
+        assert_eq!(
+            calc_pool_token_in_given_exact_tokens_out(
+                &balances,
+                &normalized_weights[..1],
+                &[5_000_000_000_000_000, 0],
+                pool_token_supply,
+                10_000_000
+            ),
+            Err(WeightedMathError::InvalidWeights)
+        );
+    }
+
+    #[test]
+    fn test_calc_pool_token_in_given_exact_tokens_out() {
+        let balances = vec![5_000_000_000_000_000_000, 1_000_000_000_000_000_000];
+        let normalized_weights = vec![500_000_000, 500_000_000];
+        let pool_token_supply = 2236021719197214567 << 1;
+        let swap_fee = 10_000_000;
+
+        // Exiting proportionally doesn't pay any swap fee
+        let amount_in = calc_pool_token_in_given_exact_tokens_out(
+            &balances,
+            &normalized_weights,
+            &[5_000_000_000_000_000, 1_000_000_000_000_000],
+            pool_token_supply,
+            swap_fee,
+        )
+        .unwrap();
+        // The invariant ratio only has 9 decimals, so the error is relative to the whole supply
+        assert!(amount_in >= pool_token_supply / 1_000);
+        assert!((amount_in - pool_token_supply / 1_000).div_up(pool_token_supply) <= 10);
+
+        // Burning the returned amount for a single token gives back the requested amount
+        for (token_index, amount_out) in [(0, balances[0] / 100), (1, balances[1] / 100)] {
+            let mut amounts_out = [0, 0];
+            amounts_out[token_index] = amount_out;
+            let amount_in = calc_pool_token_in_given_exact_tokens_out(
+                &balances,
+                &normalized_weights,
+                &amounts_out,
+                pool_token_supply,
+                swap_fee,
+            )
+            .unwrap();
+
+            let amount_out_back = calc_token_out_given_exact_pool_token_in(
+                balances[token_index],
+                normalized_weights[token_index],
+                amount_in,
+                pool_token_supply,
+                swap_fee,
+            )
+            .unwrap();
+            // Both sides tax the amount out on slightly different bases, so with fees they only round-trip to
+            // within ~fee^2
+            assert!(amount_out_back >= amount_out);
+            assert!((amount_out_back - amount_out).div_up(amount_out) < 50_000);
+            // 0.005%
+        }
+
+        assert_eq!(
+            calc_pool_token_in_given_exact_tokens_out(
+                &balances,
+                &normalized_weights,
+                &[balances[0] / 10 * 6, 0],
+                pool_token_supply,
+                swap_fee,
+            ),
+            Err(WeightedMathError::MinInvariantRatio)
+        );
+    }
+
+    #[test]
+    fn test_calc_due_protocol_fees() {
+        let previous_invariant = 1_000_000_000_000;
+        let supply = 1_000_000_000_000;
+
+        // 1% growth with half of it going to the protocol: 0.01 / 1.01 * 0.5 of the pool, so about 0.495%
+        let current_invariant = 1_010_000_000_000;
+        let fees = calc_due_protocol_fees(previous_invariant, current_invariant, supply, 500_000_000).unwrap();
+        assert_eq!(fees, 4_975_124_328);
+        let ownership = fees as f64 / (supply + fees) as f64;
+        assert!((ownership - 0.01 / 1.01 * 0.5).abs() < 1e-9);
+
+        // the whole growth to the protocol leaves the other holders with the previous invariant's worth, give or
+        // take the rounding down of the ownership
+        let fees = calc_due_protocol_fees(previous_invariant, current_invariant, supply, fixed_math::ONE).unwrap();
+        assert_eq!(fees, 9_999_999_899);
+        assert!(supply / 100 - fees < supply / fixed_math::ONE * 1_000);
+
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, current_invariant, supply, 0),
+            Some(0)
+        );
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, current_invariant, 0, 500_000_000),
+            Some(0)
+        );
+
+        // no growth, or a shrinking invariant, owes nothing
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, previous_invariant, supply, 500_000_000),
+            Some(0)
+        );
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, previous_invariant - 1, supply, 500_000_000),
+            Some(0)
+        );
+        // growth below the fixed point precision rounds away
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, previous_invariant + 1, supply, 500_000_000),
+            Some(0)
+        );
+
+        assert_eq!(calc_due_protocol_fees(0, current_invariant, supply, 500_000_000), None);
+        assert_eq!(
+            calc_due_protocol_fees(previous_invariant, current_invariant, supply, fixed_math::ONE + 1),
+            None
+        );
+        assert_eq!(calc_due_protocol_fees(1, u64::MAX, supply, 500_000_000), None);
+    }
+
+    #[test]
+    fn test_calc_invariant_checked() {
+        let balances = [4_000_000_000_000_000_000, 1_000_000_000_000_000_000];
+        let weights = [500_000_000, 500_000_000];
+        assert_eq!(
+            calc_invariant_checked(&balances, &weights),
+            calc_invariant(&balances.to_vec(), &weights.to_vec())
+        );
+
+        let balances = [1_000_000_000_000, 2_000_000_000_000, 3_000_000_000_000];
+        let weights = [200_000_000, 300_000_000, 500_000_000];
+        assert_eq!(
+            calc_invariant_checked(&balances, &weights),
+            calc_invariant(&balances.to_vec(), &weights.to_vec())
+        );
+
+        assert_eq!(
+            calc_invariant_checked(&[0, 1_000_000_000_000], &weights[..2]),
+            Err(WeightedMathError::ZeroInvariant)
+        );
+        // weights that don't normalize can push the product past u64
+        assert_eq!(
+            calc_invariant_checked(&[u64::MAX / 2, 4_000_000_000], &[fixed_math::ONE, fixed_math::ONE]),
+            Err(WeightedMathError::Overflow)
+        );
+        assert_eq!(
+            calc_invariant_checked(&balances, &weights[..2]),
+            Err(WeightedMathError::InvalidWeights)
+        );
+    }
+
+    #[test]
+    fn test_geometric_mean() {
+        let balances = [4_000_000_000_000_000_000, 1_000_000_000_000_000_000];
+        let weights = [500_000_000, 500_000_000];
+        assert_eq!(
+            geometric_mean(&balances, &weights),
+            calc_invariant(&balances.to_vec(), &weights.to_vec()).ok()
+        );
+
+        let balances = [1_000_000_000_000, 2_000_000_000_000, 3_000_000_000_000];
+        let weights = [200_000_000, 300_000_000, 500_000_000];
+        assert_eq!(
+            geometric_mean(&balances, &weights),
+            calc_invariant(&balances.to_vec(), &weights.to_vec()).ok()
+        );
+
+        assert_eq!(geometric_mean(&[], &[]), Some(fixed_math::ONE));
+        assert_eq!(geometric_mean(&balances, &weights[..2]), None);
+        assert_eq!(
+            geometric_mean(&[u64::MAX, u64::MAX], &[fixed_math::ONE, fixed_math::ONE]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ratio_errors() {
+        let balance = 1_000_000_000_000;
+
+        assert!(calc_out_given_in(balance, 500_000_000, balance, 500_000_000, 300_000_000_000).is_ok());
+        assert_eq!(
+            calc_out_given_in(balance, 500_000_000, balance, 500_000_000, 300_000_000_001),
+            Err(WeightedMathError::MaxInRatio)
+        );
+
+        assert!(calc_in_given_out(balance, 500_000_000, balance, 500_000_000, 300_000_000_000).is_ok());
+        assert_eq!(
+            calc_in_given_out(balance, 500_000_000, balance, 500_000_000, 300_000_000_001),
+            Err(WeightedMathError::MaxOutRatio)
+        );
+
+        assert!(
+            calc_token_out_given_exact_pool_token_in(balance, 500_000_000, 300_000_000_000, balance, 10_000_000)
+                .is_ok()
+        );
+        assert_eq!(
+            calc_token_out_given_exact_pool_token_in(balance, 500_000_000, 300_000_001_000, balance, 10_000_000),
+            Err(WeightedMathError::MinInvariantRatio)
+        );
+
+        assert_eq!(
+            calc_invariant(&vec![0, balance], &vec![500_000_000, 500_000_000]),
+            Err(WeightedMathError::ZeroInvariant)
+        );
+
+        // a normalized weight over ONE is rejected rather than taxed as zero
+        assert_eq!(
+            calc_pool_token_out_given_exact_token_in(balance, fixed_math::ONE + 1, 1_000_000_000, balance, 10_000_000),
+            Err(WeightedMathError::InvalidWeights)
+        );
+        assert_eq!(
+            calc_token_out_given_exact_pool_token_in(balance, fixed_math::ONE + 1, 1_000_000_000, balance, 10_000_000),
+            Err(WeightedMathError::InvalidWeights)
+        );
+    }
 }
@@ -2,6 +2,7 @@ use crate::ID;
 use anchor_lang::solana_program::instruction::AccountMeta;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use spl_token::ID as TOKEN_PROGRAM_ID;
+use spl_token_2022::ID as TOKEN_2022_PROGRAM_ID;
 use stabble_vault::ID as VAULT_PROGRAM_ID;
 
 #[derive(Copy, Clone, Debug)]
@@ -34,6 +35,7 @@ impl From<StableSwapSwap> for Vec<AccountMeta> {
             AccountMeta::new_readonly(accounts.vault_authority, false),
             AccountMeta::new_readonly(VAULT_PROGRAM_ID, false),
             AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
         ]
     }
 }
@@ -1,30 +1,78 @@
 pub mod account_meta_for_swap;
 pub mod pda;
 pub mod pool;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use crate::pool::Pool;
 use account_meta_for_swap::StableSwapSwap;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_lang::{declare_id, AccountDeserialize};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use bn::safe_math::CheckedMulDiv;
 use jupiter_amm_interface::{
-    try_get_account_data, AccountMap, Amm, AmmContext, KeyedAccount, Quote, QuoteParams, Swap, SwapAndAccountMetas,
-    SwapParams,
+    try_get_account_data, AccountMap, Amm, AmmContext, ClockRef, KeyedAccount, Quote, QuoteParams, Swap,
+    SwapAndAccountMetas, SwapParams,
 };
-use math::fixed_math::SCALE;
+use math::fixed_math::{ONE, SCALE};
+use math::format::to_decimal;
+use math::stable_math;
 use pda::get_withdraw_authority_address;
 use rust_decimal::Decimal;
 use spl_associated_token_account::get_associated_token_address;
 // use spl_token::{solana_program::program_pack::Pack, state::Account as TokenAccount};
 use stabble_vault::pda::get_vault_authority_address;
 use stabble_vault::vault::Vault;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 declare_id!("swapNyd8XiQwJ6ianp9snpu4brUqFxadzvHebnAXjJZ");
 
+/// halvings of the `max_amount_in` search range, which gets within 2^-40 of the range below the true limit
+pub const MAX_SEARCH_ITERATIONS: u32 = 40;
+
+/// state of the pool account as last loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolHealth {
+    Ok,
+    Inactive,
+    DeserializeFailed,
+}
+
+impl PoolHealth {
+    fn of(state: &Pool) -> Self {
+        if state.is_active {
+            PoolHealth::Ok
+        } else {
+            PoolHealth::Inactive
+        }
+    }
+}
+
+/// quote in whole tokens, scaled by the decimals of each mint. Prices are token in per token out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolQuote {
+    pub amount_in: Decimal,
+    pub amount_out: Decimal,
+    /// in the fee mint of the quote
+    pub fee: Decimal,
+    pub spot_price: Decimal,
+    pub effective_price: Decimal,
+    /// fraction of the spot price lost to the swap fee and slippage
+    pub price_impact: Decimal,
+}
+
 pub struct StableSwap {
     key: Pubkey,
-    state: Pool,
+    // shared between clones, so routers cloning the amm don't copy the token list; updates swap in a new one
+    state: Arc<Pool>,
     beneficiary: Option<Pubkey>,
+    clock_ref: ClockRef,
+    is_vault_active: bool,
+    // (amplification, invariant) as of the last update
+    invariant: Option<(u64, u64)>,
+    health: PoolHealth,
 }
 
 impl Clone for StableSwap {
@@ -33,21 +81,246 @@ impl Clone for StableSwap {
             key: self.key,
             state: self.state.clone(),
             beneficiary: self.beneficiary.clone(),
+            clock_ref: self.clock_ref.clone(),
+            is_vault_active: self.is_vault_active,
+            invariant: self.invariant,
+            health: self.health,
         }
     }
 }
 
-impl Amm for StableSwap {
-    fn from_keyed_account(keyed_account: &KeyedAccount, _amm_context: &AmmContext) -> Result<Self> {
-        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..]).unwrap();
+impl StableSwap {
+    /// lenient counterpart of `from_keyed_account` for indexers, never fails and records the outcome in `health`.
+    /// A pool that can't be loaded is replaced by an empty inactive one
+    pub fn from_keyed_account_unchecked(keyed_account: &KeyedAccount, amm_context: &AmmContext) -> Self {
+        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..])
+            .ok()
+            .filter(|state| state.validate_scaling().is_ok());
+        let (state, health) = match state {
+            Some(state) => {
+                let health = PoolHealth::of(&state);
+                (state, health)
+            }
+            None => {
+                let mut state = Pool::new(Pubkey::default(), 0, 0, vec![]);
+                state.is_active = false;
+                (state, PoolHealth::DeserializeFailed)
+            }
+        };
 
-        Ok(Self {
+        let mut stable_swap = Self {
             key: keyed_account.key,
-            state,
+            state: Arc::new(state),
             beneficiary: None,
+            clock_ref: amm_context.clock_ref.clone(),
+            is_vault_active: true,
+            invariant: None,
+            health,
+        };
+        stable_swap.cache_invariant();
+
+        stable_swap
+    }
+
+    pub fn health(&self) -> PoolHealth {
+        self.health
+    }
+
+    /// unwrapped reserve of `mint`, `None` if the mint isn't in the pool
+    pub fn reserve_of(&self, mint: &Pubkey) -> Option<u64> {
+        let token_index = self.state.get_token_index(*mint)?;
+        Some(
+            self.state
+                .calc_unwrapped_amount(self.state.tokens[token_index].balance, token_index),
+        )
+    }
+
+    /// total value of the reserves in units of `numeraire_mint`, each reserve valued at the pool's own spot
+    /// price at `current_ts`, `None` if the numeraire isn't in the pool or the value overflows
+    pub fn tvl_in_token(&self, current_ts: i64, numeraire_mint: &Pubkey) -> Option<u64> {
+        let numeraire_index = self.state.get_token_index(*numeraire_mint)?;
+        self.state
+            .get_reserves()
+            .iter()
+            .enumerate()
+            .try_fold(0u64, |tvl, (token_index, reserve)| {
+                let value = if token_index == numeraire_index {
+                    *reserve
+                } else {
+                    // price of the token in numeraire units
+                    let price = self.state.get_spot_price(numeraire_index, token_index, current_ts)?;
+                    reserve.checked_mul_div_down(price, ONE)?
+                };
+                tvl.checked_add(value)
+            })
+    }
+
+    /// invariant of the pool balances as of the last update, which is cached and only recomputed while the
+    /// amplification at `current_ts` differs from the one at the last update, e.g. along a ramp
+    pub fn current_invariant(&self, current_ts: i64) -> Option<u64> {
+        self.get_amplification_and_invariant(current_ts)
+            .map(|(_, invariant)| invariant)
+    }
+
+    /// largest amount of `in_mint` that still quotes at `current_ts`, `None` if either mint isn't in the pool.
+    /// Stable pools have no max in ratio, the limit is where the stable math stops producing a result, so this
+    /// binary-searches up to the amount that would take the balance in past `MAX_SAFE_BALANCE`. The search is cut
+    /// off after `MAX_SEARCH_ITERATIONS`, so the result always quotes but may fall short of the true limit by up to
+    /// 2^-40 of that range, and it assumes every amount below the limit quotes as well
+    pub fn max_amount_in(&self, current_ts: i64, in_mint: &Pubkey, out_mint: &Pubkey) -> Option<u64> {
+        let token_in_index = self.state.get_token_index(*in_mint)?;
+        let token_out_index = self.state.get_token_index(*out_mint)?;
+        let (amplification, invariant) = self.get_amplification_and_invariant(current_ts)?;
+        let quotes = |amount_in: u64| {
+            self.state
+                .get_swap_result_given_invariant(
+                    token_in_index,
+                    token_out_index,
+                    amount_in,
+                    0,
+                    amplification,
+                    invariant,
+                )
+                .is_some()
+        };
+
+        let token_in = &self.state.tokens[token_in_index];
+        let headroom = stable_math::MAX_SAFE_BALANCE.saturating_sub(token_in.balance);
+        let mut high = if token_in.scaling_up {
+            headroom / token_in.scaling_factor
+        } else {
+            // every amount that wraps to the headroom, which is the rounding it loses on the way down
+            headroom
+                .saturating_mul(token_in.scaling_factor)
+                .saturating_add(token_in.scaling_factor - 1)
+        };
+        if quotes(high) {
+            return Some(high);
+        }
+
+        let mut low = 0;
+        for _ in 0..MAX_SEARCH_ITERATIONS {
+            if low >= high {
+                break;
+            }
+            let mid = low + (high - low).div_ceil(2);
+            if quotes(mid) {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        Some(low)
+    }
+
+    /// price impact of a quote against the spot price at the current balances, as a fraction including the swap fee
+    pub fn price_impact_pct(&self, quote_params: &QuoteParams) -> Option<Decimal> {
+        let token_in_index = self.state.get_token_index(quote_params.input_mint)?;
+        let token_out_index = self.state.get_token_index(quote_params.output_mint)?;
+        let current_ts = self.clock_ref.unix_timestamp.load(Ordering::Relaxed);
+        let spot_price = self.state.get_spot_price(token_in_index, token_out_index, current_ts)?;
+        let quote = self.quote(quote_params).ok()?;
+
+        Some(price_impact(&quote, spot_price))
+    }
+
+    /// `quote` in whole tokens along with the spot and effective prices, see `PoolQuote`
+    pub fn detailed_quote(&self, quote_params: &QuoteParams) -> Result<PoolQuote> {
+        let quote = self.quote(quote_params)?;
+        let token_in_index = self
+            .state
+            .get_token_index(quote_params.input_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.input_mint))?;
+        let token_out_index = self
+            .state
+            .get_token_index(quote_params.output_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.output_mint))?;
+        let decimals_in = self.state.tokens[token_in_index].decimals;
+        let decimals_out = self.state.tokens[token_out_index].decimals;
+        let decimals_fee = if quote.fee_mint == quote_params.input_mint {
+            decimals_in
+        } else {
+            decimals_out
+        };
+        let current_ts = self.clock_ref.unix_timestamp.load(Ordering::Relaxed);
+        let raw_spot_price = self
+            .state
+            .get_spot_price(token_in_index, token_out_index, current_ts)
+            .ok_or_else(|| anyhow!("spot price cannot be computed"))?;
+
+        let amount_in = to_decimal(quote.in_amount, decimals_in);
+        let amount_out = to_decimal(quote.out_amount, decimals_out);
+        // the raw price is in base units at ONE scale, so shift it by the difference in decimals
+        let overflow = || anyhow!("spot price overflows");
+        let spot_price = Decimal::from_i128_with_scale(raw_spot_price as i128, SCALE)
+            .checked_mul(to_decimal(1, decimals_in))
+            .ok_or_else(overflow)?
+            .checked_div(to_decimal(1, decimals_out))
+            .ok_or_else(overflow)?;
+        let effective_price = if quote.out_amount == 0 {
+            Decimal::ZERO
+        } else {
+            amount_in / amount_out
+        };
+
+        Ok(PoolQuote {
+            amount_in,
+            amount_out,
+            fee: to_decimal(quote.fee_amount, decimals_fee),
+            spot_price,
+            effective_price,
+            price_impact: price_impact(&quote, raw_spot_price),
         })
     }
 
+    // The amplification only steps on whole minutes since the ramp start, so keying the cache by it invalidates
+    // the invariant exactly at the minute boundaries where it changes
+    fn get_amplification_and_invariant(&self, current_ts: i64) -> Option<(u64, u64)> {
+        let amplification = self.state.get_amplification(current_ts);
+        match self.invariant {
+            Some((cached_amplification, invariant)) if cached_amplification == amplification => {
+                Some((amplification, invariant))
+            }
+            _ => Some((amplification, self.state.current_invariant(current_ts)?)),
+        }
+    }
+
+    fn cache_invariant(&mut self) {
+        let current_ts = self.clock_ref.unix_timestamp.load(Ordering::Relaxed);
+        let amplification = self.state.get_amplification(current_ts);
+        self.invariant = self
+            .state
+            .current_invariant(current_ts)
+            .map(|invariant| (amplification, invariant));
+    }
+}
+
+impl Amm for StableSwap {
+    fn from_keyed_account(keyed_account: &KeyedAccount, amm_context: &AmmContext) -> Result<Self> {
+        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..])?;
+        // a bogus scaling factor would wrap amounts to nonsense or divide by zero
+        state.validate_scaling()?;
+        // pools outside the program's parameter ranges are only rejected with the strict feature, since the ranges
+        // are checked on-chain when the pool is created
+        #[cfg(feature = "strict")]
+        state.validate()?;
+        let health = PoolHealth::of(&state);
+
+        let mut stable_swap = Self {
+            key: keyed_account.key,
+            state: Arc::new(state),
+            beneficiary: None,
+            clock_ref: amm_context.clock_ref.clone(),
+            is_vault_active: true,
+            invariant: None,
+            health,
+        };
+        stable_swap.cache_invariant();
+
+        Ok(stable_swap)
+    }
+
     fn label(&self) -> String {
         String::from("stabble Stable Swap")
     }
@@ -72,7 +345,11 @@ impl Amm for StableSwap {
         //     .map(|token| get_associated_token_address(&vault_authority, &token.mint))
         //     .collect()
 
-        vec![self.key, self.state.vault]
+        let mut accounts = vec![self.key, self.state.vault];
+        // keep the first occurrence, so the order stays pool, vault
+        let mut seen = HashSet::new();
+        accounts.retain(|account| seen.insert(*account));
+        accounts
     }
 
     fn update(&mut self, account_map: &AccountMap) -> Result<()> {
@@ -83,25 +360,51 @@ impl Amm for StableSwap {
 
         let mut vault_data = try_get_account_data(account_map, &self.state.vault)?;
         let vault = Vault::try_deserialize(&mut vault_data)?;
+        self.is_vault_active = vault.is_active;
         self.beneficiary = Some(vault.beneficiary);
 
         let mut pool_data = try_get_account_data(account_map, &self.key)?;
-        self.state = Pool::try_deserialize(&mut pool_data)?;
+        self.state = Arc::new(Pool::try_deserialize(&mut pool_data)?);
+        self.health = PoolHealth::of(&self.state);
+        self.cache_invariant();
 
         Ok(())
     }
 
     fn quote(&self, quote_params: &QuoteParams) -> Result<Quote> {
-        let token_in_index = self.state.get_token_index(quote_params.input_mint);
-        let token_out_index = self.state.get_token_index(quote_params.output_mint);
+        if !self.is_active() {
+            return Err(anyhow!("pool {} is not active", self.key));
+        }
+
+        let token_in_index = self
+            .state
+            .get_token_index(quote_params.input_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.input_mint))?;
+        let token_out_index = self
+            .state
+            .get_token_index(quote_params.output_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.output_mint))?;
+
+        // a clock that was never updated reads zero, which quotes at the initial amplification
+        let current_ts = self.clock_ref.unix_timestamp.load(Ordering::Relaxed);
 
         let amount_in = self.state.calc_rounded_amount(quote_params.amount, token_in_index);
-        let (amount_out, amount_fee) =
-            self.state
-                .get_swap_result(token_in_index, token_out_index, quote_params.amount, 0);
+        let (amount_out, amount_fee) = self
+            .get_amplification_and_invariant(current_ts)
+            .and_then(|(amplification, invariant)| {
+                self.state.get_swap_result_given_invariant(
+                    token_in_index,
+                    token_out_index,
+                    amount_in,
+                    0,
+                    amplification,
+                    invariant,
+                )
+            })
+            .ok_or_else(|| anyhow!("amount in {} cannot be quoted by this pool", amount_in))?;
 
         Ok(Quote {
-            fee_pct: Decimal::from_i128_with_scale(self.state.swap_fee as i128, SCALE),
+            fee_pct: self.state.swap_fee_decimal(),
             in_amount: amount_in,
             out_amount: amount_out,
             fee_amount: amount_fee,
@@ -111,6 +414,10 @@ impl Amm for StableSwap {
     }
 
     fn get_swap_and_account_metas(&self, swap_params: &SwapParams) -> Result<SwapAndAccountMetas> {
+        if !self.is_active() {
+            return Err(anyhow!("pool {} is not active", self.key));
+        }
+
         let SwapParams {
             token_transfer_authority,
             source_token_account,
@@ -123,8 +430,10 @@ impl Amm for StableSwap {
         let vault_authority = get_vault_authority_address(&self.state.vault);
         let vault_source_token_account = get_associated_token_address(&vault_authority, &source_mint);
         let vault_destination_token_account = get_associated_token_address(&vault_authority, &destination_mint);
-        let beneficiary_destination_token_account =
-            get_associated_token_address(&self.beneficiary.as_ref().unwrap(), &destination_mint);
+        let beneficiary = self
+            .beneficiary
+            .ok_or_else(|| anyhow!("vault {} not loaded, call update first", self.state.vault))?;
+        let beneficiary_destination_token_account = get_associated_token_address(&beneficiary, &destination_mint);
 
         Ok(SwapAndAccountMetas {
             swap: Swap::TokenSwap, // StabbleWeightedSWap
@@ -144,7 +453,484 @@ impl Amm for StableSwap {
         })
     }
 
+    fn program_dependencies(&self) -> Vec<(Pubkey, String)> {
+        vec![(stabble_vault::ID, String::from("stabble_vault"))]
+    }
+
     fn clone_amm(&self) -> Box<dyn Amm + Send + Sync> {
         Box::new(self.clone())
     }
+
+    fn is_active(&self) -> bool {
+        self.state.is_active && self.is_vault_active
+    }
+}
+
+/// 1 - spot_price / effective_price, with prices as token in per token out and the spot price at ONE scale
+fn price_impact(quote: &Quote, spot_price: u64) -> Decimal {
+    if quote.in_amount == 0 || quote.out_amount == 0 {
+        return Decimal::ZERO;
+    }
+
+    let ratio = (quote.out_amount as u128 * spot_price as u128) / quote.in_amount as u128;
+    let price_impact = (ONE as u128).saturating_sub(ratio);
+
+    Decimal::from_i128_with_scale(price_impact as i128, SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::tests::pool;
+    use anchor_lang::AccountSerialize;
+    use jupiter_amm_interface::SwapMode;
+    use solana_sdk::account::Account;
+
+    fn stable_swap() -> StableSwap {
+        StableSwap {
+            key: Pubkey::new_unique(),
+            state: Arc::new(pool()),
+            beneficiary: Some(Pubkey::new_unique()),
+            clock_ref: ClockRef::default(),
+            is_vault_active: true,
+            invariant: None,
+            health: PoolHealth::Ok,
+        }
+    }
+
+    fn swap_params(quote_params: &QuoteParams) -> SwapParams<'static, 'static> {
+        SwapParams {
+            in_amount: quote_params.amount,
+            out_amount: 0,
+            source_mint: quote_params.input_mint,
+            destination_mint: quote_params.output_mint,
+            source_token_account: Pubkey::new_unique(),
+            destination_token_account: Pubkey::new_unique(),
+            token_transfer_authority: Pubkey::new_unique(),
+            open_order_address: None,
+            quote_mint_to_referrer: None,
+            jupiter_program_id: &ID,
+            missing_dynamic_accounts_as_default: false,
+        }
+    }
+
+    #[test]
+    fn test_price_impact_pct() {
+        let stable_swap = stable_swap();
+        let mut quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: stable_swap.state.tokens[0].mint,
+            output_mint: stable_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let fee_pct = stable_swap.state.swap_fee_decimal();
+
+        // small trades against a balanced pool pay little more than the swap fee
+        let small_impact = stable_swap.price_impact_pct(&quote_params).unwrap();
+        assert!(small_impact >= fee_pct);
+        assert!(small_impact - fee_pct < Decimal::new(2, 5));
+
+        // and the impact grows as the trade drains the pool
+        quote_params.amount = 500_000_000;
+        let large_impact = stable_swap.price_impact_pct(&quote_params).unwrap();
+        assert!(large_impact > small_impact);
+        quote_params.amount = 5_000_000_000;
+        assert!(stable_swap.price_impact_pct(&quote_params).unwrap() > large_impact);
+
+        quote_params.output_mint = Pubkey::new_unique();
+        assert_eq!(stable_swap.price_impact_pct(&quote_params), None);
+    }
+
+    #[test]
+    fn test_account_metas() {
+        let stable_swap = stable_swap();
+        let quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: stable_swap.state.tokens[0].mint,
+            output_mint: stable_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let account_metas = stable_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .unwrap()
+            .account_metas;
+        let program_ids: Vec<Pubkey> = account_metas[account_metas.len() - 3..]
+            .iter()
+            .map(|account_meta| account_meta.pubkey)
+            .collect();
+
+        assert_eq!(account_metas.len(), 14);
+        assert_eq!(account_metas[0].pubkey, ID);
+        assert_eq!(program_ids, [stabble_vault::ID, spl_token::ID, spl_token_2022::ID]);
+        assert_eq!(
+            stable_swap.program_dependencies(),
+            [(stabble_vault::ID, String::from("stabble_vault"))]
+        );
+    }
+
+    #[test]
+    fn test_get_accounts_to_update() {
+        let mut stable_swap = stable_swap();
+        assert_eq!(
+            stable_swap.get_accounts_to_update(),
+            vec![stable_swap.key, stable_swap.state.vault]
+        );
+
+        // a pool stored at its own vault address lists it once
+        stable_swap.key = stable_swap.state.vault;
+        assert_eq!(stable_swap.get_accounts_to_update(), vec![stable_swap.state.vault]);
+    }
+
+    #[test]
+    fn test_clone_shares_state() {
+        let stable_swap = stable_swap();
+
+        // clones point at the same pool, token list included, instead of copying it
+        let clones: Vec<StableSwap> = (0..1_000).map(|_| stable_swap.clone()).collect();
+        assert_eq!(Arc::strong_count(&stable_swap.state), 1_001);
+        for clone in clones.iter() {
+            assert!(Arc::ptr_eq(&clone.state, &stable_swap.state));
+            assert_eq!(clone.state.tokens.as_ptr(), stable_swap.state.tokens.as_ptr());
+        }
+        drop(clones);
+        assert_eq!(Arc::strong_count(&stable_swap.state), 1);
+
+        // mutating a clone copies the pool first and leaves the shared one alone
+        let mut mutated = stable_swap.clone();
+        Arc::make_mut(&mut mutated.state).tokens[0].balance *= 2;
+        assert!(!Arc::ptr_eq(&mutated.state, &stable_swap.state));
+        assert_eq!(mutated.state.tokens[0].balance, 2 * stable_swap.state.tokens[0].balance);
+    }
+
+    #[test]
+    fn test_tvl_in_token() {
+        let mut stable_swap = stable_swap();
+        let mints: Vec<Pubkey> = stable_swap.state.tokens.iter().map(|token| token.mint).collect();
+        let balance = stable_swap.state.tokens[0].balance;
+
+        // a balanced pool prices both tokens at par
+        assert_eq!(stable_swap.tvl_in_token(0, &mints[0]), Some(2 * balance));
+        assert_eq!(stable_swap.tvl_in_token(0, &mints[1]), Some(2 * balance));
+        assert_eq!(stable_swap.tvl_in_token(0, &Pubkey::new_unique()), None);
+
+        // off balance the spot price stays near one, so the total is close to the sum of the reserves
+        Arc::make_mut(&mut stable_swap.state).tokens[1].balance = 3 * balance;
+        let reserves: u64 = stable_swap.state.get_reserves().iter().sum();
+        for mint in mints.iter() {
+            let tvl = stable_swap.tvl_in_token(0, mint).unwrap();
+            assert!(tvl.abs_diff(reserves) < reserves / 10);
+        }
+        // the scarcer token is worth more, so the total reads higher in units of the abundant one
+        assert!(stable_swap.tvl_in_token(0, &mints[1]).unwrap() > stable_swap.tvl_in_token(0, &mints[0]).unwrap());
+
+        // reserves are valued in token amounts, not wrapped balances
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.tokens[1].balance = balance;
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        assert_eq!(stable_swap.tvl_in_token(0, &mints[0]), Some(2 * balance));
+        assert_eq!(stable_swap.tvl_in_token(0, &mints[1]), Some(2 * balance / 1_000));
+    }
+
+    #[test]
+    fn test_reserve_of() {
+        let mut stable_swap = stable_swap();
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        let mints: Vec<Pubkey> = stable_swap.state.tokens.iter().map(|token| token.mint).collect();
+
+        assert_eq!(
+            stable_swap.reserve_of(&mints[0]),
+            Some(stable_swap.state.tokens[0].balance)
+        );
+        assert_eq!(
+            stable_swap.reserve_of(&mints[1]),
+            Some(stable_swap.state.tokens[1].balance / 1_000)
+        );
+        assert_eq!(
+            mints
+                .iter()
+                .map(|mint| stable_swap.reserve_of(mint).unwrap())
+                .collect::<Vec<_>>(),
+            stable_swap.state.get_reserves()
+        );
+        assert_eq!(stable_swap.reserve_of(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn test_inactive() {
+        let mut stable_swap = stable_swap();
+        let quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: stable_swap.state.tokens[0].mint,
+            output_mint: stable_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        assert!(stable_swap.is_active());
+        assert!(stable_swap.quote(&quote_params).is_ok());
+        assert!(stable_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_ok());
+
+        stable_swap.is_vault_active = false;
+        assert!(!stable_swap.is_active());
+        assert!(stable_swap.quote(&quote_params).is_err());
+        assert!(stable_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_err());
+
+        stable_swap.is_vault_active = true;
+        Arc::make_mut(&mut stable_swap.state).is_active = false;
+        assert!(!stable_swap.is_active());
+        assert!(stable_swap.quote(&quote_params).is_err());
+        assert!(stable_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_err());
+    }
+
+    #[test]
+    fn test_current_invariant() {
+        let mut stable_swap = stable_swap();
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.amp_initial_factor = 100;
+        state.amp_target_factor = 200;
+        state.ramp_start_ts = 1_700_000_000;
+        state.ramp_stop_ts = 1_700_086_400;
+        let invariant = stable_swap.state.current_invariant(0).unwrap();
+        assert_eq!(stable_swap.current_invariant(0), Some(invariant));
+
+        stable_swap.cache_invariant();
+        assert_eq!(
+            stable_swap.invariant,
+            Some((100 * stable_math::AMP_PRECISION, invariant))
+        );
+
+        // balances only change on update, so the cached invariant stays until then
+        Arc::make_mut(&mut stable_swap.state).tokens[0].balance *= 2;
+        assert_eq!(stable_swap.current_invariant(0), Some(invariant));
+        assert_eq!(stable_swap.current_invariant(1_700_000_000), Some(invariant));
+
+        // a different amplification along the ramp is recomputed
+        let ramping_invariant = stable_swap.state.current_invariant(1_700_043_200).unwrap();
+        assert_ne!(ramping_invariant, invariant);
+        assert_eq!(stable_swap.current_invariant(1_700_043_200), Some(ramping_invariant));
+
+        stable_swap.cache_invariant();
+        assert_ne!(stable_swap.current_invariant(0), Some(invariant));
+        assert_eq!(stable_swap.current_invariant(0), stable_swap.state.current_invariant(0));
+    }
+
+    #[test]
+    fn test_amplification_cache_minute_boundary() {
+        let mut stable_swap = stable_swap();
+        let ramp_start_ts = 1_700_000_000;
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.tokens[0].balance *= 2;
+        state.amp_initial_factor = 100;
+        state.amp_target_factor = 200;
+        state.ramp_start_ts = ramp_start_ts;
+        state.ramp_stop_ts = ramp_start_ts + 86_400;
+
+        stable_swap
+            .clock_ref
+            .unix_timestamp
+            .store(ramp_start_ts + 90, Ordering::Relaxed);
+        stable_swap.cache_invariant();
+        let (amplification, invariant) = stable_swap.invariant.unwrap();
+        assert_eq!(stable_swap.state.current_invariant(ramp_start_ts + 90), Some(invariant));
+
+        // moving balances without an update exposes whether the cached invariant is reused
+        Arc::make_mut(&mut stable_swap.state).tokens[1].balance *= 2;
+        for current_ts in [ramp_start_ts + 60, ramp_start_ts + 90, ramp_start_ts + 119] {
+            assert_eq!(
+                stable_swap.get_amplification_and_invariant(current_ts),
+                Some((amplification, invariant))
+            );
+        }
+        for current_ts in [ramp_start_ts + 59, ramp_start_ts + 120] {
+            let (next_amplification, next_invariant) = stable_swap.get_amplification_and_invariant(current_ts).unwrap();
+            assert_ne!(next_amplification, amplification);
+            assert_eq!(stable_swap.state.current_invariant(current_ts), Some(next_invariant));
+        }
+
+        // quotes use the cached invariant within the minute
+        let quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: stable_swap.state.tokens[0].mint,
+            output_mint: stable_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let quote = stable_swap.quote(&quote_params).unwrap();
+        let (amount_out, _) = stable_swap
+            .state
+            .get_swap_result_given_invariant(0, 1, 1_000_000, 0, amplification, invariant)
+            .unwrap();
+        assert_eq!(quote.out_amount, amount_out);
+    }
+
+    #[test]
+    fn test_from_keyed_account_unchecked() {
+        let amm_context = AmmContext {
+            clock_ref: ClockRef::default(),
+        };
+        let keyed_account = |state: &Pool| {
+            let mut data = vec![];
+            state.try_serialize(&mut data).unwrap();
+            KeyedAccount {
+                key: Pubkey::new_unique(),
+                account: Account {
+                    lamports: 1,
+                    data,
+                    owner: ID,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+                params: None,
+            }
+        };
+
+        let state = pool();
+        let loaded = StableSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::Ok);
+        assert_eq!(*loaded.state, state);
+        assert_eq!(loaded.current_invariant(0), state.current_invariant(0));
+        assert_eq!(
+            StableSwap::from_keyed_account(&keyed_account(&state), &amm_context)
+                .unwrap()
+                .health(),
+            PoolHealth::Ok
+        );
+
+        let mut state = pool();
+        state.is_active = false;
+        let loaded = StableSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::Inactive);
+        assert_eq!(*loaded.state, state);
+        assert_eq!(
+            StableSwap::from_keyed_account(&keyed_account(&state), &amm_context)
+                .unwrap()
+                .health(),
+            PoolHealth::Inactive
+        );
+
+        // malformed pools only load through the lenient constructor
+        let mut state = pool();
+        state.tokens[0].scaling_factor = 0;
+        assert!(StableSwap::from_keyed_account(&keyed_account(&state), &amm_context).is_err());
+        let loaded = StableSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::DeserializeFailed);
+        assert!(!loaded.is_active());
+        assert!(loaded.get_reserve_mints().is_empty());
+
+        let mut keyed_account = keyed_account(&pool());
+        keyed_account.account.data.truncate(16);
+        assert!(StableSwap::from_keyed_account(&keyed_account, &amm_context).is_err());
+        let loaded = StableSwap::from_keyed_account_unchecked(&keyed_account, &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::DeserializeFailed);
+    }
+
+    #[test]
+    fn test_max_amount_in() {
+        let mut stable_swap = stable_swap();
+        let in_mint = stable_swap.state.tokens[0].mint;
+        let out_mint = stable_swap.state.tokens[1].mint;
+        let quote = |stable_swap: &StableSwap, amount: u64| {
+            stable_swap.quote(&QuoteParams {
+                amount,
+                input_mint: in_mint,
+                output_mint: out_mint,
+                swap_mode: SwapMode::ExactIn,
+            })
+        };
+        assert_eq!(stable_swap.max_amount_in(0, &in_mint, &Pubkey::new_unique()), None);
+
+        // the limit is where the balance in would pass the max safe balance
+        let limit = stable_math::MAX_SAFE_BALANCE - stable_swap.state.tokens[0].balance;
+        let max_amount_in = stable_swap.max_amount_in(0, &in_mint, &out_mint).unwrap();
+        assert!(max_amount_in <= limit && max_amount_in >= limit - (limit >> MAX_SEARCH_ITERATIONS));
+        assert!(quote(&stable_swap, max_amount_in).is_ok());
+        assert!(quote(&stable_swap, limit + 1).is_err());
+
+        // narrow enough for the search to land on it exactly
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.tokens[0].balance = stable_math::MAX_SAFE_BALANCE - 1_000_000_000;
+        state.tokens[1].balance = stable_math::MAX_SAFE_BALANCE;
+        stable_swap.cache_invariant();
+        let max_amount_in = stable_swap.max_amount_in(0, &in_mint, &out_mint).unwrap();
+        assert_eq!(max_amount_in, 1_000_000_000);
+        assert!(quote(&stable_swap, max_amount_in).is_ok());
+        assert!(quote(&stable_swap, max_amount_in + 1).is_err());
+    }
+
+    #[test]
+    fn test_detailed_quote() {
+        let mut stable_swap = stable_swap();
+        let state = Arc::make_mut(&mut stable_swap.state);
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        let mut quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: stable_swap.state.tokens[0].mint,
+            output_mint: stable_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+
+        let quote = stable_swap.quote(&quote_params).unwrap();
+        let detailed_quote = stable_swap.detailed_quote(&quote_params).unwrap();
+        assert_eq!(detailed_quote.amount_in, Decimal::new(quote.in_amount as i64, 9));
+        assert_eq!(detailed_quote.amount_out, Decimal::new(quote.out_amount as i64, 6));
+        assert_eq!(detailed_quote.fee, Decimal::new(quote.fee_amount as i64, 6));
+        // equal wrapped balances are worth the same in whole tokens whatever the decimals
+        assert_eq!(detailed_quote.spot_price, Decimal::ONE);
+        assert_eq!(
+            detailed_quote.effective_price,
+            detailed_quote.amount_in / detailed_quote.amount_out
+        );
+        assert!(detailed_quote.effective_price > detailed_quote.spot_price);
+        assert_eq!(
+            Some(detailed_quote.price_impact),
+            stable_swap.price_impact_pct(&quote_params)
+        );
+
+        // the other way round the price is quoted in the 6 decimal token
+        quote_params.input_mint = stable_swap.state.tokens[1].mint;
+        quote_params.output_mint = stable_swap.state.tokens[0].mint;
+        quote_params.amount = 1_000;
+        let quote = stable_swap.quote(&quote_params).unwrap();
+        let detailed_quote = stable_swap.detailed_quote(&quote_params).unwrap();
+        assert_eq!(detailed_quote.amount_in, Decimal::new(quote.in_amount as i64, 6));
+        assert_eq!(detailed_quote.amount_out, Decimal::new(quote.out_amount as i64, 9));
+        assert_eq!(detailed_quote.spot_price, Decimal::ONE);
+
+        quote_params.output_mint = Pubkey::new_unique();
+        assert!(stable_swap.detailed_quote(&quote_params).is_err());
+    }
+
+    #[test]
+    fn test_zero_amount_quote() {
+        let stable_swap = stable_swap();
+
+        let quote = stable_swap
+            .quote(&QuoteParams {
+                amount: 0,
+                input_mint: stable_swap.state.tokens[0].mint,
+                output_mint: stable_swap.state.tokens[1].mint,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .unwrap();
+        assert_eq!((quote.in_amount, quote.out_amount, quote.fee_amount), (0, 0, 0));
+
+        // without touching the stable math, which can't handle an empty balance
+        let mut state = pool();
+        state.tokens[1].balance = 0;
+        assert_eq!(
+            state.get_swap_result_given_invariant(0, 1, 0, 0, 100_000, 1_000_000_000),
+            Some((0, 0))
+        );
+        assert_eq!(
+            state.get_swap_result_given_invariant(0, 1, 1_000, 0, 100_000, 1_000_000_000),
+            None
+        );
+    }
 }
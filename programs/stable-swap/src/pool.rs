@@ -1,30 +1,54 @@
 use anchor_lang::prelude::borsh;
-use anchor_lang::{
-    account,
-    solana_program::{clock::Clock, pubkey::Pubkey},
-    AnchorDeserialize, AnchorSerialize,
+use anchor_lang::{account, solana_program::pubkey::Pubkey, AnchorDeserialize, AnchorSerialize};
+use bn::{
+    safe_math::{CheckedDivCeil, CheckedMulDiv},
+    uint192, U192,
 };
-use bn::safe_math::CheckedMulDiv;
 use math::{
-    fixed_math::{FixedComplement, FixedMul},
+    base_pool_math,
+    error::{PoolError, ScalingError},
+    fixed_math::{FixedComplement, FixedDiv, FixedMul, SCALE},
     stable_math, swap_fee_math,
 };
-use spl_token::solana_program::sysvar::Sysvar;
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde_with::{As, DisplayFromStr};
 
-#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy)]
+/// largest power of ten a scaling factor can be, 10^19 is the last one that fits u64
+pub const MAX_SCALING_EXPONENT: u32 = 19;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy, Debug)]
 pub struct PoolToken {
-    pub mint: Pubkey,        // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub mint: Pubkey, // immutable
     pub decimals: u8,        // immutable
     pub scaling_up: bool,    // immutable
     pub scaling_factor: u64, // immutable
     pub balance: u64,
 }
 
+/// amplification ramp as seen at a given timestamp, amplifications are scaled by `AMP_PRECISION`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct AmpRampStatus {
+    pub current: u64,
+    pub target: u64,
+    pub start_ts: i64,
+    pub stop_ts: i64,
+    pub is_ramping: bool,
+}
+
 #[account]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Eq, PartialEq, Debug)]
 pub struct Pool {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
     pub owner: Pubkey,
-    pub vault: Pubkey,      // immutable
-    pub mint: Pubkey,       // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub vault: Pubkey, // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub mint: Pubkey, // immutable
     pub authority_bump: u8, // immutable
     pub is_active: bool,
     pub amp_initial_factor: u16,
@@ -33,14 +57,34 @@ pub struct Pool {
     pub ramp_stop_ts: i64,
     pub swap_fee: u64,
     pub tokens: Vec<PoolToken>,
+    #[cfg_attr(feature = "serde", serde(with = "As::<Option<DisplayFromStr>>"))]
     pub pending_owner: Option<Pubkey>,
 }
 
 impl Pool {
-    pub fn get_amplification(&self) -> u64 {
+    /// active pool over `tokens` at a fixed amplification for tests and simulations, the on-chain path is
+    /// `try_deserialize`. The owner, mint and authority bump are left at their defaults since quoting never reads them
+    pub fn new(vault: Pubkey, amp_factor: u16, swap_fee: u64, tokens: Vec<PoolToken>) -> Self {
+        Self {
+            owner: Pubkey::default(),
+            vault,
+            mint: Pubkey::default(),
+            authority_bump: 0,
+            is_active: true,
+            amp_initial_factor: amp_factor,
+            amp_target_factor: amp_factor,
+            ramp_start_ts: 0,
+            ramp_stop_ts: 0,
+            swap_fee,
+            tokens,
+            pending_owner: None,
+        }
+    }
+
+    /// amplification at `current_ts`, ramping linearly from the initial to the target factor
+    pub fn get_amplification(&self, current_ts: i64) -> u64 {
         let amp_initial_factor = self.amp_initial_factor as u64;
         let amp_target_factor = self.amp_target_factor as u64;
-        let current_ts = Clock::get().unwrap().unix_timestamp;
 
         if current_ts <= self.ramp_start_ts {
             amp_initial_factor.saturating_mul(stable_math::AMP_PRECISION)
@@ -69,17 +113,107 @@ impl Pool {
         }
     }
 
+    /// `get_amplification` with checked arithmetic throughout, `None` where a misconfigured ramp would overflow and
+    /// the saturating version would quietly clamp to a wrong amplification
+    pub fn get_amplification_checked(&self, current_ts: i64) -> Option<u64> {
+        let amp_initial = (self.amp_initial_factor as u64).checked_mul(stable_math::AMP_PRECISION)?;
+        let amp_target = (self.amp_target_factor as u64).checked_mul(stable_math::AMP_PRECISION)?;
+
+        if current_ts <= self.ramp_start_ts {
+            Some(amp_initial)
+        } else if current_ts >= self.ramp_stop_ts {
+            Some(amp_target)
+        } else {
+            let ramp_elapsed = u64::try_from(current_ts.checked_sub(self.ramp_start_ts)?).ok()? / 60 * 60;
+            let ramp_duration = u64::try_from(self.ramp_stop_ts.checked_sub(self.ramp_start_ts)?).ok()?;
+            let amp_offset = amp_initial
+                .abs_diff(amp_target)
+                .checked_mul_div_down(ramp_elapsed, ramp_duration)?;
+            if amp_initial <= amp_target {
+                amp_initial.checked_add(amp_offset)
+            } else {
+                amp_initial.checked_sub(amp_offset)
+            }
+        }
+    }
+
+    /// amplification ramp at `current_ts`, a ramp that hasn't started or has already finished is not ramping
+    pub fn amp_ramp_status(&self, current_ts: i64) -> AmpRampStatus {
+        AmpRampStatus {
+            current: self.get_amplification(current_ts),
+            target: (self.amp_target_factor as u64).saturating_mul(stable_math::AMP_PRECISION),
+            start_ts: self.ramp_start_ts,
+            stop_ts: self.ramp_stop_ts,
+            is_ramping: self.amp_initial_factor != self.amp_target_factor
+                && current_ts > self.ramp_start_ts
+                && current_ts < self.ramp_stop_ts,
+        }
+    }
+
+    /// checks every invariant a deserialized pool has to hold: token count, swap fee, amplification factors,
+    /// scaling factors and unique mints
+    pub fn validate(&self) -> Result<(), PoolError> {
+        if !(stable_math::MIN_TOKENS..=stable_math::MAX_TOKENS).contains(&self.tokens.len()) {
+            return Err(PoolError::InvalidTokenCount);
+        }
+        if !(stable_math::MIN_SWAP_FEE..=stable_math::MAX_SWAP_FEE).contains(&self.swap_fee) {
+            return Err(PoolError::InvalidSwapFee);
+        }
+        let amp_range = stable_math::MIN_AMP..=stable_math::MAX_AMP;
+        if !amp_range.contains(&self.amp_initial_factor) || !amp_range.contains(&self.amp_target_factor) {
+            return Err(PoolError::InvalidAmplification);
+        }
+        self.validate_scaling()?;
+        // pools hold a handful of tokens, so a pairwise scan is cheaper than hashing
+        for (token_index, token) in self.tokens.iter().enumerate() {
+            if self.tokens[..token_index].iter().any(|other| other.mint == token.mint) {
+                return Err(PoolError::DuplicateMint);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// checks each scaling factor is a power of ten, the only decimal scales the wrapping supports
+    pub fn validate_scaling(&self) -> Result<(), ScalingError> {
+        for token in self.tokens.iter() {
+            if !(0..=MAX_SCALING_EXPONENT).any(|exponent| token.scaling_factor == 10u64.pow(exponent)) {
+                return Err(ScalingError::InvalidScalingFactor);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// invariant at the amplification for `current_ts`, returns `None` if the stable math fails to converge
+    pub fn current_invariant(&self, current_ts: i64) -> Option<u64> {
+        stable_math::calc_invariant(self.get_amplification(current_ts), &self.get_balances()).ok()
+    }
+
+    /// swap fee as a fraction, e.g. 1_000_000 is 0.001 (0.1%), which is what Jupiter expects in `fee_pct`
+    pub fn swap_fee_decimal(&self) -> Decimal {
+        Decimal::from_i128_with_scale(self.swap_fee as i128, SCALE)
+    }
+
     pub fn get_balances(&self) -> Vec<u64> {
         self.tokens.iter().map(|token| token.balance).collect()
     }
 
-    pub fn get_token_index(&self, mint: Pubkey) -> usize {
-        self.tokens
-            .iter()
-            .enumerate()
-            .find(|(_, token)| token.mint == mint)
-            .unwrap()
-            .0
+    /// balances unwrapped to token amounts
+    pub fn get_reserves(&self) -> Vec<u64> {
+        (0..self.tokens.len())
+            .map(|token_index| self.calc_unwrapped_amount(self.tokens[token_index].balance, token_index))
+            .collect()
+    }
+
+    pub fn get_token_index(&self, mint: Pubkey) -> Option<usize> {
+        self.tokens.iter().position(|token| token.mint == mint)
+    }
+
+    /// decimals of `mint`, `None` if the mint isn't in the pool
+    pub fn decimals_of(&self, mint: &Pubkey) -> Option<u8> {
+        let token_index = self.get_token_index(*mint)?;
+        Some(self.tokens[token_index].decimals)
     }
 
     /// scaling up/down from token amount to wrapped balance amount
@@ -104,6 +238,41 @@ impl Pool {
         }
     }
 
+    /// token amounts in for minting exactly `bpt_out` pool tokens, returns `None` for an empty pool
+    pub fn quote_add_liquidity_proportional(&self, bpt_out: u64, pool_token_supply: u64) -> Option<Vec<u64>> {
+        let wrapped_amounts_in =
+            base_pool_math::compute_proportional_amounts_in(&self.get_balances(), pool_token_supply, bpt_out)?;
+
+        // unwrapping rounds up as well, so the user never pays less than the pool tokens are worth
+        wrapped_amounts_in
+            .iter()
+            .enumerate()
+            .map(|(token_index, wrapped_amount_in)| {
+                let token = &self.tokens[token_index];
+                if token.scaling_factor == 1 || !token.scaling_up {
+                    Some(self.calc_unwrapped_amount(*wrapped_amount_in, token_index))
+                } else {
+                    wrapped_amount_in.checked_div_up(token.scaling_factor)
+                }
+            })
+            .collect()
+    }
+
+    /// token amounts out for burning `bpt_in` pool tokens across all tokens, returns `None` for an empty pool
+    pub fn quote_remove_liquidity_proportional(&self, bpt_in: u64, pool_token_supply: u64) -> Option<Vec<u64>> {
+        let wrapped_amounts_out =
+            base_pool_math::compute_proportional_amounts_out(&self.get_balances(), pool_token_supply, bpt_in)?;
+
+        // unwrapping rounds down as well, so the pool never pays out more than the pool tokens are worth
+        Some(
+            wrapped_amounts_out
+                .iter()
+                .enumerate()
+                .map(|(token_index, wrapped_amount_out)| self.calc_unwrapped_amount(*wrapped_amount_out, token_index))
+                .collect(),
+        )
+    }
+
     /// round down token amount not to send the lost amount from wrapped balance amount when it scaled down
     pub fn calc_rounded_amount(&self, amount: u64, token_index: usize) -> u64 {
         if self.tokens[token_index].scaling_up {
@@ -113,35 +282,785 @@ impl Pool {
         }
     }
 
-    /// estimated swap amount out
+    /// estimated pool token amount out for the given token amounts in
+    pub fn quote_add_liquidity(&self, current_ts: i64, amounts_in: &[u64], pool_token_supply: u64) -> Option<u64> {
+        if amounts_in.len() != self.tokens.len() {
+            return None;
+        }
+
+        let amplification = self.get_amplification(current_ts);
+        let balances = self.get_balances();
+        let current_invariant = stable_math::calc_invariant(amplification, &balances).ok()?;
+        let wrapped_amounts_in = amounts_in
+            .iter()
+            .enumerate()
+            .map(|(token_index, amount)| self.calc_wrapped_amount(*amount, token_index))
+            .collect();
+
+        stable_math::calc_pool_token_out_given_exact_tokens_in(
+            amplification,
+            &balances,
+            &wrapped_amounts_in,
+            pool_token_supply,
+            current_invariant,
+            self.swap_fee,
+        )
+        .ok()
+    }
+
+    /// estimated swap amount out, returns `None` if the stable math fails to converge
     pub fn get_swap_result(
         &self,
         token_in_index: usize,
         token_out_index: usize,
         amount_in: u64,
         x_amount: u64,
-    ) -> (u64, u64) {
-        let amplification = self.get_amplification();
-        let balances = self.get_balances();
-        let current_invariant = stable_math::calc_invariant(amplification, &balances).unwrap();
+        current_ts: i64,
+    ) -> Option<(u64, u64)> {
+        self.get_swap_results_batch(token_in_index, token_out_index, &[amount_in], x_amount, current_ts)?
+            .pop()
+    }
+
+    /// estimated swap amounts out for each amount in, computing the invariant once for all of them, returns `None`
+    /// if the stable math fails for any of them
+    pub fn get_swap_results_batch(
+        &self,
+        token_in_index: usize,
+        token_out_index: usize,
+        amounts_in: &[u64],
+        x_amount: u64,
+        current_ts: i64,
+    ) -> Option<Vec<(u64, u64)>> {
+        let amplification = self.get_amplification(current_ts);
+        let current_invariant = self.current_invariant(current_ts)?;
+
+        amounts_in
+            .iter()
+            .map(|amount_in| {
+                self.get_swap_result_given_invariant(
+                    token_in_index,
+                    token_out_index,
+                    *amount_in,
+                    x_amount,
+                    amplification,
+                    current_invariant,
+                )
+            })
+            .collect()
+    }
+
+    /// estimated swap amount out against an amplification and invariant computed beforehand for the current
+    /// balances, returns `None` if the stable math fails to converge
+    pub fn get_swap_result_given_invariant(
+        &self,
+        token_in_index: usize,
+        token_out_index: usize,
+        amount_in: u64,
+        x_amount: u64,
+        amplification: u64,
+        current_invariant: u64,
+    ) -> Option<(u64, u64)> {
+        // routers probe with zero amounts, which shouldn't depend on the stable math converging
+        if amount_in == 0 {
+            return Some((0, 0));
+        }
+
         let swap_fee = swap_fee_math::calc_swap_fee_in_discount(self.swap_fee, x_amount);
 
         let wrapped_amount_in = self.calc_wrapped_amount(amount_in, token_in_index);
         let wrapped_amount_out_without_fee = stable_math::calc_out_given_in(
             amplification,
-            &balances,
+            &self.get_balances(),
             token_in_index,
             token_out_index,
             wrapped_amount_in,
             current_invariant,
         )
-        .unwrap();
+        .ok()?;
 
         let wrapped_amount_out = wrapped_amount_out_without_fee.mul_down(swap_fee.complement());
         let wrapped_amount_fee = wrapped_amount_out_without_fee - wrapped_amount_out;
         let amount_out = self.calc_unwrapped_amount(wrapped_amount_out, token_out_index);
         let amount_fee = self.calc_unwrapped_amount(wrapped_amount_fee, token_out_index);
 
-        (amount_out, amount_fee)
+        Some((amount_out, amount_fee))
+    }
+
+    /// spot price of token out in terms of token in, in unwrapped token amounts at ONE scale
+    pub fn get_spot_price(&self, token_in_index: usize, token_out_index: usize, current_ts: i64) -> Option<u64> {
+        let amplification = self.get_amplification(current_ts);
+        let balances = self.get_balances();
+        let current_invariant = stable_math::calc_invariant(amplification, &balances).ok()?;
+        let wrapped_price = stable_math::calc_spot_price(
+            amplification,
+            &balances,
+            token_in_index,
+            token_out_index,
+            current_invariant,
+        )
+        .ok()?;
+
+        let token_in = &self.tokens[token_in_index];
+        let token_out = &self.tokens[token_out_index];
+
+        // balances are wrapped, so scale the price back to token amounts
+        let mut numerator = uint192!(wrapped_price);
+        let mut denominator = uint192!(1);
+        if token_in.scaling_up {
+            denominator = denominator.checked_mul(uint192!(token_in.scaling_factor))?;
+        } else {
+            numerator = numerator.checked_mul(uint192!(token_in.scaling_factor))?;
+        }
+        if token_out.scaling_up {
+            numerator = numerator.checked_mul(uint192!(token_out.scaling_factor))?;
+        } else {
+            denominator = denominator.checked_mul(uint192!(token_out.scaling_factor))?;
+        }
+
+        let price = numerator.checked_div(denominator)?;
+        if price > uint192!(u64::MAX) {
+            None
+        } else {
+            Some(price.as_u64())
+        }
+    }
+
+    /// estimated swap amount in and fee for an exact amount out, returns `None` if the amount out exceeds the
+    /// balance or the stable math fails to converge
+    pub fn get_swap_result_exact_out(
+        &self,
+        token_in_index: usize,
+        token_out_index: usize,
+        amount_out: u64,
+        x_amount: u64,
+        current_ts: i64,
+    ) -> Option<(u64, u64)> {
+        let wrapped_amount_out = self.calc_wrapped_amount(amount_out, token_out_index);
+        if wrapped_amount_out >= self.tokens[token_out_index].balance {
+            return None;
+        }
+
+        let amplification = self.get_amplification(current_ts);
+        let balances = self.get_balances();
+        let current_invariant = stable_math::calc_invariant(amplification, &balances).ok()?;
+        let swap_fee = swap_fee_math::calc_swap_fee_in_discount(self.swap_fee, x_amount);
+
+        let wrapped_amount_in_without_fee = stable_math::calc_in_given_out(
+            amplification,
+            &balances,
+            token_in_index,
+            token_out_index,
+            wrapped_amount_out,
+            current_invariant,
+        )
+        .ok()?;
+
+        // the fee is taken from the amount in, so gross it up and round against the user
+        let wrapped_amount_in = wrapped_amount_in_without_fee.div_up(swap_fee.complement());
+        let wrapped_amount_fee = wrapped_amount_in - wrapped_amount_in_without_fee;
+        let token_in = &self.tokens[token_in_index];
+        if token_in.scaling_factor == 1 || !token_in.scaling_up {
+            Some((
+                self.calc_unwrapped_amount(wrapped_amount_in, token_in_index),
+                self.calc_unwrapped_amount(wrapped_amount_fee, token_in_index),
+            ))
+        } else {
+            Some((
+                wrapped_amount_in.checked_div_up(token_in.scaling_factor)?,
+                wrapped_amount_fee.checked_div_up(token_in.scaling_factor)?,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use anchor_lang::{error::ErrorCode, AccountDeserialize, AccountSerialize};
+    use math::fixed_math::ONE;
+    use std::str::FromStr;
+
+    const RAMP_START_TS: i64 = 1_700_000_000;
+    const RAMP_STOP_TS: i64 = RAMP_START_TS + 86_400;
+
+    const BALANCE: u64 = 1_000_000_000;
+
+    pub(crate) fn pool() -> Pool {
+        let token = PoolToken {
+            mint: Pubkey::new_unique(),
+            decimals: 9,
+            scaling_up: true,
+            scaling_factor: 1,
+            balance: BALANCE,
+        };
+        Pool {
+            owner: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            authority_bump: 255,
+            is_active: true,
+            amp_initial_factor: 100,
+            amp_target_factor: 100,
+            ramp_start_ts: 0,
+            ramp_stop_ts: 0,
+            swap_fee: 1_000_000,
+            tokens: vec![
+                token,
+                PoolToken {
+                    mint: Pubkey::new_unique(),
+                    ..token
+                },
+            ],
+            pending_owner: None,
+        }
+    }
+
+    fn pool_data() -> Vec<u8> {
+        let mut data = vec![];
+        pool().try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_new() {
+        let vault = Pubkey::new_unique();
+        let tokens = pool().tokens;
+        let new_pool = Pool::new(vault, 100, 1_000_000, tokens.clone());
+        assert_eq!(new_pool.vault, vault);
+        assert_eq!(new_pool.tokens, tokens);
+        assert!(new_pool.is_active);
+        assert_eq!(
+            new_pool.amp_ramp_status(RAMP_START_TS),
+            AmpRampStatus {
+                current: 100 * stable_math::AMP_PRECISION,
+                target: 100 * stable_math::AMP_PRECISION,
+                start_ts: 0,
+                stop_ts: 0,
+                is_ramping: false,
+            }
+        );
+        assert_eq!(
+            new_pool.get_swap_result(0, 1, 1_000_000, 0, RAMP_START_TS),
+            Pool { tokens, ..pool() }.get_swap_result(0, 1, 1_000_000, 0, RAMP_START_TS)
+        );
+    }
+
+    #[test]
+    fn test_get_spot_price() {
+        let mut pool = pool();
+        assert_eq!(pool.get_spot_price(0, 1, 0), Some(ONE));
+
+        pool.tokens[0].balance = 1_500_000_000_000;
+        pool.tokens[1].balance = 500_000_000_000;
+        let spot_price = pool.get_spot_price(0, 1, 0).unwrap();
+        assert_eq!(spot_price, 1017535241);
+        assert_eq!(pool.get_spot_price(1, 0, 0), Some(982766944));
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(pool.get_spot_price(0, 1, 0), Some(spot_price * 1_000));
+        assert_eq!(pool.get_spot_price(1, 0, 0), Some(982766));
+
+        pool.tokens[1].balance = 0;
+        assert_eq!(pool.get_spot_price(0, 1, 0), None);
+    }
+
+    #[test]
+    fn test_swap_fee_decimal() {
+        let mut state = pool();
+        for (swap_fee, expected) in [
+            (1_000_000, "0.001"),
+            (10_000, "0.00001"),
+            (25_000_000, "0.025"),
+            (1_000, "0.000001"),
+            (0, "0"),
+            (ONE, "1"),
+        ] {
+            state.swap_fee = swap_fee;
+            assert_eq!(state.swap_fee_decimal(), Decimal::from_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(pool().validate(), Ok(()));
+
+        let mut state = pool();
+        state.tokens.truncate(1);
+        assert_eq!(state.validate(), Err(PoolError::InvalidTokenCount));
+
+        let mut state = pool();
+        let token = state.tokens[0];
+        state.tokens = (0..=stable_math::MAX_TOKENS)
+            .map(|_| PoolToken {
+                mint: Pubkey::new_unique(),
+                ..token
+            })
+            .collect();
+        assert_eq!(state.validate(), Err(PoolError::InvalidTokenCount));
+
+        for swap_fee in [0, stable_math::MIN_SWAP_FEE - 1, stable_math::MAX_SWAP_FEE + 1] {
+            let mut state = pool();
+            state.swap_fee = swap_fee;
+            assert_eq!(state.validate(), Err(PoolError::InvalidSwapFee));
+        }
+        for swap_fee in [stable_math::MIN_SWAP_FEE, stable_math::MAX_SWAP_FEE] {
+            let mut state = pool();
+            state.swap_fee = swap_fee;
+            assert_eq!(state.validate(), Ok(()));
+        }
+
+        // both ends of a ramp have to be in range
+        for (amp_initial_factor, amp_target_factor) in [
+            (stable_math::MIN_AMP - 1, 100),
+            (100, stable_math::MIN_AMP - 1),
+            (stable_math::MAX_AMP + 1, 100),
+            (100, stable_math::MAX_AMP + 1),
+        ] {
+            let mut state = pool();
+            state.amp_initial_factor = amp_initial_factor;
+            state.amp_target_factor = amp_target_factor;
+            assert_eq!(state.validate(), Err(PoolError::InvalidAmplification));
+        }
+        let mut state = pool();
+        state.amp_initial_factor = stable_math::MIN_AMP;
+        state.amp_target_factor = stable_math::MAX_AMP;
+        assert_eq!(state.validate(), Ok(()));
+
+        let mut state = pool();
+        state.tokens[1].scaling_factor = 999;
+        assert_eq!(state.validate(), Err(PoolError::InvalidScalingFactor));
+
+        let mut state = pool();
+        state.tokens[1].mint = state.tokens[0].mint;
+        assert_eq!(state.validate(), Err(PoolError::DuplicateMint));
+    }
+
+    #[test]
+    fn test_validate_scaling() {
+        let mut pool = pool();
+        assert_eq!(pool.validate_scaling(), Ok(()));
+
+        for scaling_factor in [10, 1_000, 1_000_000_000, 10_000_000_000_000_000_000] {
+            pool.tokens[1].scaling_factor = scaling_factor;
+            assert_eq!(pool.validate_scaling(), Ok(()));
+        }
+        for scaling_factor in [0, 2, 999, 1_001, 5_000, u64::MAX] {
+            pool.tokens[1].scaling_factor = scaling_factor;
+            assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+        }
+
+        // zero is rejected either way it scales
+        pool.tokens[1].scaling_factor = 1;
+        pool.tokens[0].scaling_factor = 0;
+        pool.tokens[0].scaling_up = false;
+        assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+        pool.tokens[0].scaling_up = true;
+        assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+    }
+
+    #[test]
+    fn test_quote_add_liquidity_proportional() {
+        let mut pool = pool();
+        let pool_token_supply = 2 * BALANCE;
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(0, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(1, pool_token_supply),
+            Some(vec![1, 1])
+        );
+
+        // the initial deposit is up to the caller
+        assert_eq!(pool.quote_add_liquidity_proportional(BALANCE, 0), None);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10_000])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(1, pool_token_supply),
+            Some(vec![1, 1])
+        );
+    }
+
+    #[test]
+    fn test_quote_remove_liquidity_proportional() {
+        let mut pool = pool();
+        let pool_token_supply = 2 * BALANCE;
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply, pool_token_supply),
+            Some(vec![BALANCE, BALANCE])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(0, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(1, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(pool.quote_remove_liquidity_proportional(BALANCE, 0), None);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10_000])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(2_000, pool_token_supply),
+            Some(vec![1_000, 1])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(1_998, pool_token_supply),
+            Some(vec![999, 0])
+        );
+    }
+
+    #[test]
+    fn test_quote_add_liquidity() {
+        let mut pool = pool();
+        pool.amp_initial_factor = 5_000;
+        pool.amp_target_factor = 5_000;
+        pool.swap_fee = 100_000;
+        pool.tokens[0].balance = 894_520_800_000_000;
+        pool.tokens[1].balance = 467_581_800_000_000;
+        let pool_token_supply = pool.current_invariant(0).unwrap();
+
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[1_000_000_000_000_000, 1_000_000_000_000_000], pool_token_supply),
+            Some(1999977982041509)
+        );
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[0, 2_000_000_000_000], pool_token_supply),
+            Some(2000047447155)
+        );
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[1_000_000_000_000, 1_000_000_000_000], pool_token_supply),
+            Some(1999994325732)
+        );
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[0, 2_000_000_000], pool_token_supply),
+            Some(2000047447155)
+        );
+
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[1_000_000_000_000], pool_token_supply),
+            None
+        );
+        assert_eq!(
+            pool.quote_add_liquidity(0, &[1_000_000_000_000, 1_000_000_000_000, 1], pool_token_supply),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_swap_result_exact_out() {
+        let mut pool = pool();
+        pool.tokens[0].balance = 2 * BALANCE;
+
+        let (amount_out, _) = pool.get_swap_result(0, 1, 100_000_000, 0, 0).unwrap();
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, amount_out, 0, 0).unwrap();
+        // The fee is charged on the amount in rather than the amount out, so it comes out slightly lower
+        assert_eq!(amount_out, 98939652);
+        assert_eq!(amount_in, 99999853);
+        assert_eq!(amount_fee, 100000);
+
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, 100_000_000, 0, 0).unwrap();
+        assert_eq!(amount_fee, amount_in.mul_up(pool.swap_fee));
+        let (amount_out, _) = pool.get_swap_result(0, 1, amount_in - amount_fee, 0, 0).unwrap();
+        assert!(amount_out.abs_diff(100_000_000.mul_down(pool.swap_fee.complement())) <= 1);
+
+        // the fee discount applies on the input side
+        let (discounted_amount_in, discounted_fee) = pool
+            .get_swap_result_exact_out(0, 1, 100_000_000, 1_000_000_000_000_000, 0)
+            .unwrap();
+        assert!(discounted_fee < amount_fee);
+        assert!(discounted_amount_in < amount_in);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance rounds the amount in up
+        pool.tokens[0].decimals = 6;
+        pool.tokens[0].scaling_factor = 1_000;
+        let (scaled_amount_in, scaled_fee) = pool.get_swap_result_exact_out(0, 1, 100_000_000, 0, 0).unwrap();
+        assert_eq!(scaled_amount_in, amount_in.div_ceil(1_000));
+        assert_eq!(scaled_fee, amount_fee.div_ceil(1_000));
+
+        // cannot take out the whole balance
+        assert!(pool.get_swap_result_exact_out(0, 1, BALANCE - 1, 0, 0).is_some());
+        assert!(pool.get_swap_result_exact_out(0, 1, BALANCE, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_get_swap_results_batch() {
+        let mut pool = pool();
+        pool.tokens[0].balance = 2 * BALANCE;
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+
+        let amounts_in = [0, 1, 1_000, 1_000_000, 100_000_000, 500_000_000];
+        for (token_in_index, token_out_index) in [(0, 1), (1, 0)] {
+            let results = pool
+                .get_swap_results_batch(token_in_index, token_out_index, &amounts_in, 0, 0)
+                .unwrap();
+            assert_eq!(results.len(), amounts_in.len());
+            for (amount_in, result) in amounts_in.iter().zip(results) {
+                assert_eq!(
+                    Some(result),
+                    pool.get_swap_result(token_in_index, token_out_index, *amount_in, 0, 0)
+                );
+            }
+        }
+
+        assert_eq!(pool.get_swap_results_batch(0, 1, &[], 0, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn test_get_reserves() {
+        let mut pool = pool();
+        pool.tokens.push(PoolToken {
+            mint: Pubkey::new_unique(),
+            ..pool.tokens[0]
+        });
+        assert_eq!(pool.get_reserves(), pool.get_balances());
+
+        // 6 decimals token scaled up and 12 decimals token scaled down to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        pool.tokens[2].decimals = 12;
+        pool.tokens[2].scaling_up = false;
+        pool.tokens[2].scaling_factor = 1_000;
+        assert_eq!(pool.get_reserves(), [BALANCE, BALANCE / 1_000, BALANCE * 1_000]);
+        assert_eq!(pool.get_balances(), [BALANCE; 3]);
+    }
+
+    #[test]
+    fn test_try_deserialize_truncated() {
+        let data = pool_data();
+        assert!(Pool::try_deserialize(&mut &data[..]).is_ok());
+
+        // Cut off in the middle of the last token, right before `pending_owner`
+        let truncated = &data[..data.len() - 21];
+        assert_eq!(
+            Pool::try_deserialize(&mut &truncated[..]).err(),
+            Some(ErrorCode::AccountDidNotDeserialize.into())
+        );
+        assert_eq!(
+            Pool::try_deserialize(&mut &data[..8]).err(),
+            Some(ErrorCode::AccountDidNotDeserialize.into())
+        );
+        assert_eq!(
+            Pool::try_deserialize(&mut &data[..4]).err(),
+            Some(ErrorCode::AccountDiscriminatorNotFound.into())
+        );
+    }
+
+    #[test]
+    fn test_get_amplification_ramping() {
+        let mut pool = pool();
+        pool.amp_initial_factor = 100;
+        pool.amp_target_factor = 200;
+        pool.ramp_start_ts = RAMP_START_TS;
+        pool.ramp_stop_ts = RAMP_STOP_TS;
+
+        let initial = 100 * stable_math::AMP_PRECISION;
+        let target = 200 * stable_math::AMP_PRECISION;
+        let halfway = RAMP_START_TS + 43_200;
+
+        // zero and stale timestamps fall back to the initial amplification
+        assert_eq!(pool.get_amplification(0), initial);
+        assert_eq!(pool.get_amplification(-1), initial);
+        assert_eq!(pool.get_amplification(RAMP_START_TS), initial);
+        assert_eq!(pool.get_amplification(halfway), 150 * stable_math::AMP_PRECISION);
+        // elapsed time is counted in whole minutes
+        assert_eq!(pool.get_amplification(halfway + 59), 150 * stable_math::AMP_PRECISION);
+        assert_eq!(pool.get_amplification(RAMP_STOP_TS), target);
+        assert_eq!(pool.get_amplification(i64::MAX), target);
+
+        // ramping down mirrors ramping up
+        pool.amp_initial_factor = 200;
+        pool.amp_target_factor = 100;
+        assert_eq!(pool.get_amplification(0), target);
+        assert_eq!(pool.get_amplification(halfway), 150 * stable_math::AMP_PRECISION);
+        assert_eq!(pool.get_amplification(RAMP_STOP_TS), initial);
+    }
+
+    #[test]
+    fn test_get_amplification_checked() {
+        let mut pool = pool();
+        pool.amp_initial_factor = 100;
+        pool.amp_target_factor = 200;
+        pool.ramp_start_ts = RAMP_START_TS;
+        pool.ramp_stop_ts = RAMP_STOP_TS;
+
+        for amp_target_factor in [200, 100, 50, u16::MAX] {
+            pool.amp_target_factor = amp_target_factor;
+            for current_ts in [
+                i64::MIN,
+                0,
+                RAMP_START_TS,
+                RAMP_START_TS + 1,
+                RAMP_START_TS + 43_259,
+                RAMP_STOP_TS - 1,
+                RAMP_STOP_TS,
+                i64::MAX,
+            ] {
+                assert_eq!(
+                    pool.get_amplification_checked(current_ts),
+                    Some(pool.get_amplification(current_ts))
+                );
+            }
+        }
+
+        // the ramp length overflows i64, which the saturating version clamps to almost the end of the ramp when
+        // it's only halfway through
+        pool.amp_target_factor = 200;
+        pool.ramp_start_ts = i64::MIN;
+        pool.ramp_stop_ts = i64::MAX;
+        assert!(pool.get_amplification(0) > 199 * stable_math::AMP_PRECISION);
+        assert_eq!(pool.get_amplification_checked(0), None);
+        assert_eq!(
+            pool.get_amplification_checked(i64::MIN),
+            Some(100 * stable_math::AMP_PRECISION)
+        );
+        assert_eq!(
+            pool.get_amplification_checked(i64::MAX),
+            Some(200 * stable_math::AMP_PRECISION)
+        );
+    }
+
+    #[test]
+    fn test_amp_ramp_status() {
+        let mut pool = pool();
+        pool.amp_initial_factor = 100;
+        pool.amp_target_factor = 200;
+        pool.ramp_start_ts = RAMP_START_TS;
+        pool.ramp_stop_ts = RAMP_STOP_TS;
+
+        let status = |current, is_ramping| AmpRampStatus {
+            current: current * stable_math::AMP_PRECISION,
+            target: 200 * stable_math::AMP_PRECISION,
+            start_ts: RAMP_START_TS,
+            stop_ts: RAMP_STOP_TS,
+            is_ramping,
+        };
+
+        // a scheduled ramp is not ramping until it starts
+        assert_eq!(pool.amp_ramp_status(0), status(100, false));
+        assert_eq!(pool.amp_ramp_status(RAMP_START_TS), status(100, false));
+        assert_eq!(pool.amp_ramp_status(RAMP_START_TS + 1), status(100, true));
+        assert_eq!(pool.amp_ramp_status(RAMP_START_TS + 43_200), status(150, true));
+        let last_minute = pool.amp_ramp_status(RAMP_STOP_TS - 1);
+        assert!(last_minute.is_ramping);
+        assert!(last_minute.current < last_minute.target);
+        assert_eq!(pool.amp_ramp_status(RAMP_STOP_TS), status(200, false));
+        assert_eq!(pool.amp_ramp_status(i64::MAX), status(200, false));
+
+        // a fixed amplification never ramps
+        pool.amp_initial_factor = 200;
+        assert_eq!(pool.amp_ramp_status(RAMP_START_TS + 43_200), status(200, false));
+    }
+
+    #[test]
+    fn test_get_swap_result_ramping() {
+        let mut pool = pool();
+        pool.tokens[0].balance = 2_000_000_000;
+        pool.amp_initial_factor = 10;
+        pool.amp_target_factor = 1000;
+        pool.ramp_start_ts = RAMP_START_TS;
+        pool.ramp_stop_ts = RAMP_STOP_TS;
+
+        let amount_in = 100_000_000;
+        let (out_start, _) = pool.get_swap_result(0, 1, amount_in, 0, RAMP_START_TS).unwrap();
+        let (out_halfway, _) = pool
+            .get_swap_result(0, 1, amount_in, 0, RAMP_START_TS + 43_200)
+            .unwrap();
+        let (out_stop, _) = pool.get_swap_result(0, 1, amount_in, 0, RAMP_STOP_TS).unwrap();
+
+        // a higher amplification keeps an imbalanced pool closer to its peg
+        assert!(out_start < out_halfway);
+        assert!(out_halfway < out_stop);
+        assert_eq!(pool.get_swap_result(0, 1, amount_in, 0, 0).unwrap().0, out_start);
+    }
+
+    #[test]
+    fn test_try_serialize_round_trip() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for num_tokens in 2..=stable_math::MAX_TOKENS {
+            let mut pool = pool();
+            pool.is_active = next() % 2 == 0;
+            pool.amp_initial_factor = next() as u16;
+            pool.amp_target_factor = next() as u16;
+            pool.ramp_start_ts = next() as i64;
+            pool.ramp_stop_ts = next() as i64;
+            pool.swap_fee = next();
+            pool.pending_owner = (next() % 2 == 0).then(Pubkey::new_unique);
+            pool.tokens = (0..num_tokens)
+                .map(|_| PoolToken {
+                    mint: Pubkey::new_unique(),
+                    decimals: next() as u8,
+                    scaling_up: next() % 2 == 0,
+                    scaling_factor: next(),
+                    balance: next(),
+                })
+                .collect();
+
+            let mut data = vec![];
+            pool.try_serialize(&mut data).unwrap();
+            assert_eq!(Pool::try_deserialize(&mut &data[..]).unwrap(), pool);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut pool = pool();
+        pool.pending_owner = Some(Pubkey::new_unique());
+
+        let json = serde_json::to_value(&pool).unwrap();
+        assert_eq!(json["owner"], pool.owner.to_string());
+        assert_eq!(json["pending_owner"], pool.pending_owner.unwrap().to_string());
+        assert_eq!(json["tokens"][1]["mint"], pool.tokens[1].mint.to_string());
+        assert_eq!(json["tokens"][1]["balance"], BALANCE);
+        assert_eq!(serde_json::from_value::<Pool>(json).unwrap(), pool);
+
+        pool.pending_owner = None;
+        let json = serde_json::to_string(&pool).unwrap();
+        assert_eq!(serde_json::from_str::<Pool>(&json).unwrap(), pool);
+    }
+
+    #[test]
+    fn test_decimals_of() {
+        let mut pool = pool();
+        pool.tokens[1].decimals = 6;
+
+        assert_eq!(pool.decimals_of(&pool.tokens[0].mint), Some(9));
+        assert_eq!(pool.decimals_of(&pool.tokens[1].mint), Some(6));
+        assert_eq!(pool.decimals_of(&Pubkey::new_unique()), None);
     }
 }
@@ -1,14 +1,82 @@
 use anchor_lang::prelude::borsh;
 use anchor_lang::{account, solana_program::pubkey::Pubkey, AnchorDeserialize, AnchorSerialize};
+#[cfg(feature = "serde")]
+use serde_with::{As, DisplayFromStr};
 
 #[account]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Eq, PartialEq, Debug)]
 pub struct Vault {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
     pub admin: Pubkey,
-    pub withdraw_authority: Pubkey,  // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub withdraw_authority: Pubkey, // immutable
     pub withdraw_authority_bump: u8, // immutable
     pub authority_bump: u8,          // immutable
     pub is_active: bool,
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
     pub beneficiary: Pubkey,
     pub beneficiary_fee: u64,
+    #[cfg_attr(feature = "serde", serde(with = "As::<Option<DisplayFromStr>>"))]
     pub pending_admin: Option<Pubkey>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AccountDeserialize, Discriminator};
+
+    #[test]
+    fn test_try_deserialize_layout() {
+        let admin = Pubkey::new_unique();
+        let withdraw_authority = Pubkey::new_unique();
+        let beneficiary = Pubkey::new_unique();
+
+        let mut data = Vault::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(admin.as_ref());
+        data.extend_from_slice(withdraw_authority.as_ref());
+        data.extend_from_slice(&[254, 253, 1]);
+        data.extend_from_slice(beneficiary.as_ref());
+        data.extend_from_slice(&250_000_000u64.to_le_bytes());
+        data.push(0);
+
+        let vault = Vault::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(vault.admin, admin);
+        assert_eq!(vault.withdraw_authority, withdraw_authority);
+        assert_eq!(vault.withdraw_authority_bump, 254);
+        assert_eq!(vault.authority_bump, 253);
+        assert!(vault.is_active);
+        assert_eq!(vault.beneficiary, beneficiary);
+        assert_eq!(vault.beneficiary_fee, 250_000_000);
+        assert_eq!(vault.pending_admin, None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let vault = Vault {
+            admin: Pubkey::new_unique(),
+            withdraw_authority: Pubkey::new_unique(),
+            withdraw_authority_bump: 254,
+            authority_bump: 253,
+            is_active: true,
+            beneficiary: Pubkey::new_unique(),
+            beneficiary_fee: 250_000_000,
+            pending_admin: Some(Pubkey::new_unique()),
+        };
+
+        let json = serde_json::to_value(&vault).unwrap();
+        assert_eq!(json["admin"], vault.admin.to_string());
+        assert_eq!(json["pending_admin"], vault.pending_admin.unwrap().to_string());
+        assert_eq!(json["beneficiary_fee"], 250_000_000);
+        assert_eq!(serde_json::from_value::<Vault>(json).unwrap(), vault);
+
+        let vault = Vault {
+            pending_admin: None,
+            ..vault
+        };
+        let json = serde_json::to_string(&vault).unwrap();
+        assert!(json.contains(r#""pending_admin":null"#));
+        assert_eq!(serde_json::from_str::<Vault>(&json).unwrap(), vault);
+    }
+}
@@ -1,30 +1,77 @@
 pub mod account_meta_for_swap;
 pub mod pda;
 pub mod pool;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use crate::pool::Pool;
 use account_meta_for_swap::WeightedSwapSwap;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_lang::{declare_id, AccountDeserialize};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use bn::safe_math::CheckedMulDiv;
 use jupiter_amm_interface::{
-    try_get_account_data, AccountMap, Amm, AmmContext, KeyedAccount, Quote, QuoteParams, Swap, SwapAndAccountMetas,
-    SwapParams,
+    try_get_account_data, AccountMap, Amm, AmmContext, ClockRef, KeyedAccount, Quote, QuoteParams, Swap,
+    SwapAndAccountMetas, SwapMode, SwapParams,
 };
-use math::fixed_math::SCALE;
+use math::fixed_math::{FixedComplement, FixedDiv, FixedMul, ONE, SCALE};
+use math::format::to_decimal;
 use pda::get_withdraw_authority_address;
 use rust_decimal::Decimal;
-use spl_associated_token_account::get_associated_token_address;
-// use spl_token::{solana_program::program_pack::Pack, state::Account as TokenAccount};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::{
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+    state::{Account as TokenAccount, Mint},
+};
 use stabble_vault::pda::get_vault_authority_address;
 use stabble_vault::vault::Vault;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 declare_id!("swapFpHZwjELNnjvThjajtiVmkz3yPQEHjLtka2fwHW");
 
+/// state of the pool account as last loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolHealth {
+    Ok,
+    Inactive,
+    DeserializeFailed,
+}
+
+impl PoolHealth {
+    fn of(state: &Pool) -> Self {
+        if state.is_active {
+            PoolHealth::Ok
+        } else {
+            PoolHealth::Inactive
+        }
+    }
+}
+
+/// quote in whole tokens, scaled by the decimals of each mint. Prices are token in per token out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolQuote {
+    pub amount_in: Decimal,
+    pub amount_out: Decimal,
+    /// in the fee mint of the quote
+    pub fee: Decimal,
+    pub spot_price: Decimal,
+    pub effective_price: Decimal,
+    /// fraction of the spot price lost to the swap fee and slippage
+    pub price_impact: Decimal,
+}
+
 pub struct WeightedSwap {
     key: Pubkey,
-    state: Pool,
+    // shared between clones, so routers cloning the amm don't copy the token list; updates swap in a new one
+    state: Arc<Pool>,
     beneficiary: Option<Pubkey>,
+    beneficiary_fee: u64,
+    clock_ref: ClockRef,
+    is_vault_active: bool,
+    token_programs: Vec<Pubkey>,
+    health: PoolHealth,
 }
 
 impl Clone for WeightedSwap {
@@ -33,18 +80,198 @@ impl Clone for WeightedSwap {
             key: self.key,
             state: self.state.clone(),
             beneficiary: self.beneficiary.clone(),
+            beneficiary_fee: self.beneficiary_fee,
+            clock_ref: self.clock_ref.clone(),
+            is_vault_active: self.is_vault_active,
+            token_programs: self.token_programs.clone(),
+            health: self.health,
         }
     }
 }
 
+impl WeightedSwap {
+    /// lenient counterpart of `from_keyed_account` for indexers, never fails and records the outcome in `health`.
+    /// A pool that can't be loaded is replaced by an empty inactive one
+    pub fn from_keyed_account_unchecked(keyed_account: &KeyedAccount, amm_context: &AmmContext) -> Self {
+        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..])
+            .ok()
+            .filter(|state| state.validate_scaling().is_ok() && state.validate_weights().is_ok());
+        let (state, health) = match state {
+            Some(state) => {
+                let health = PoolHealth::of(&state);
+                (state, health)
+            }
+            None => {
+                let mut state = Pool::new(Pubkey::default(), 0, vec![]);
+                state.is_active = false;
+                (state, PoolHealth::DeserializeFailed)
+            }
+        };
+        let token_programs = vec![spl_token::ID; state.tokens.len()];
+
+        Self {
+            key: keyed_account.key,
+            state: Arc::new(state),
+            beneficiary: None,
+            beneficiary_fee: 0,
+            clock_ref: amm_context.clock_ref.clone(),
+            is_vault_active: true,
+            token_programs,
+            health,
+        }
+    }
+
+    pub fn health(&self) -> PoolHealth {
+        self.health
+    }
+
+    /// unwrapped reserve of `mint`, `None` if the mint isn't in the pool
+    pub fn reserve_of(&self, mint: &Pubkey) -> Option<u64> {
+        let token_index = self.state.get_token_index(*mint)?;
+        Some(
+            self.state
+                .calc_unwrapped_amount(self.state.tokens[token_index].balance, token_index),
+        )
+    }
+
+    /// total value of the reserves in units of `numeraire_mint`, each reserve valued at the pool's own spot
+    /// price, `None` if the numeraire isn't in the pool or the value overflows
+    pub fn tvl_in_token(&self, numeraire_mint: &Pubkey) -> Option<u64> {
+        let numeraire_index = self.state.get_token_index(*numeraire_mint)?;
+        self.state
+            .get_reserves()
+            .iter()
+            .enumerate()
+            .try_fold(0u64, |tvl, (token_index, reserve)| {
+                let value = if token_index == numeraire_index {
+                    *reserve
+                } else {
+                    // price of the token in numeraire units
+                    let price = self.state.get_spot_price(numeraire_index, token_index)?;
+                    reserve.checked_mul_div_down(price, ONE)?
+                };
+                tvl.checked_add(value)
+            })
+    }
+
+    /// largest amount of `in_mint` an exact in quote accepts before hitting the max in ratio, `None` if either
+    /// mint isn't in the pool
+    pub fn max_amount_in(&self, in_mint: &Pubkey, out_mint: &Pubkey) -> Option<u64> {
+        let token_in_index = self.state.get_token_index(*in_mint)?;
+        self.state.get_token_index(*out_mint)?;
+        self.state.get_max_amount_in(token_in_index)
+    }
+
+    /// largest amount of `out_mint` an exact out quote accepts before hitting the max out ratio, `None` if either
+    /// mint isn't in the pool
+    pub fn max_amount_out(&self, in_mint: &Pubkey, out_mint: &Pubkey) -> Option<u64> {
+        self.state.get_token_index(*in_mint)?;
+        let token_out_index = self.state.get_token_index(*out_mint)?;
+        // the beneficiary's cut is grossed up on top of the quoted amount out
+        Some(
+            self.state
+                .get_max_amount_out(token_out_index)
+                .mul_down(self.beneficiary_fee.complement()),
+        )
+    }
+
+    /// vault token accounts of the pool tokens, under the token program that owns each mint
+    pub fn get_vault_token_addresses(&self) -> Vec<Pubkey> {
+        vault_token_addresses(&self.state, &self.token_programs)
+    }
+
+    /// token program that owns the mint, as read from the mint account on the last update
+    pub fn token_program_of(&self, mint: &Pubkey) -> Result<Pubkey> {
+        let token_index = self
+            .state
+            .get_token_index(*mint)
+            .ok_or_else(|| anyhow!("mint {} is not in pool {}", mint, self.key))?;
+        Ok(self.token_programs[token_index])
+    }
+
+    /// price impact of the quoted swap against the spot price, between 0 and 1
+    pub fn price_impact_pct(&self, quote_params: &QuoteParams) -> Option<Decimal> {
+        let token_in_index = self.state.get_token_index(quote_params.input_mint)?;
+        let token_out_index = self.state.get_token_index(quote_params.output_mint)?;
+        let spot_price = self.state.get_spot_price(token_in_index, token_out_index)?;
+        let quote = self.quote(quote_params).ok()?;
+
+        Some(price_impact(&quote, spot_price))
+    }
+
+    /// `quote` in whole tokens along with the spot and effective prices, see `PoolQuote`
+    pub fn detailed_quote(&self, quote_params: &QuoteParams) -> Result<PoolQuote> {
+        let quote = self.quote(quote_params)?;
+        let token_in_index = self
+            .state
+            .get_token_index(quote_params.input_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.input_mint))?;
+        let token_out_index = self
+            .state
+            .get_token_index(quote_params.output_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.output_mint))?;
+        let decimals_in = self.state.tokens[token_in_index].decimals;
+        let decimals_out = self.state.tokens[token_out_index].decimals;
+        let decimals_fee = if quote.fee_mint == quote_params.input_mint {
+            decimals_in
+        } else {
+            decimals_out
+        };
+        let raw_spot_price = self
+            .state
+            .get_spot_price(token_in_index, token_out_index)
+            .ok_or_else(|| anyhow!("spot price cannot be computed"))?;
+
+        let amount_in = to_decimal(quote.in_amount, decimals_in);
+        let amount_out = to_decimal(quote.out_amount, decimals_out);
+        // the raw price is in base units at ONE scale, so shift it by the difference in decimals
+        let overflow = || anyhow!("spot price overflows");
+        let spot_price = Decimal::from_i128_with_scale(raw_spot_price as i128, SCALE)
+            .checked_mul(to_decimal(1, decimals_in))
+            .ok_or_else(overflow)?
+            .checked_div(to_decimal(1, decimals_out))
+            .ok_or_else(overflow)?;
+        let effective_price = if quote.out_amount == 0 {
+            Decimal::ZERO
+        } else {
+            amount_in / amount_out
+        };
+
+        Ok(PoolQuote {
+            amount_in,
+            amount_out,
+            fee: to_decimal(quote.fee_amount, decimals_fee),
+            spot_price,
+            effective_price,
+            price_impact: price_impact(&quote, raw_spot_price),
+        })
+    }
+}
+
 impl Amm for WeightedSwap {
-    fn from_keyed_account(keyed_account: &KeyedAccount, _amm_context: &AmmContext) -> Result<Self> {
-        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..]).unwrap();
+    fn from_keyed_account(keyed_account: &KeyedAccount, amm_context: &AmmContext) -> Result<Self> {
+        let state = Pool::try_deserialize(&mut &keyed_account.account.data[..])?;
+        // a bogus scaling factor would wrap amounts to nonsense or divide by zero
+        state.validate_scaling()?;
+        // weights that don't normalize would quote silently wrong amounts
+        state.validate_weights()?;
+        // pools outside the program's parameter ranges are only rejected with the strict feature, since the ranges
+        // are checked on-chain when the pool is created
+        #[cfg(feature = "strict")]
+        state.validate()?;
+        // corrected from the mint owners on update
+        let token_programs = vec![spl_token::ID; state.tokens.len()];
+        let health = PoolHealth::of(&state);
 
         Ok(Self {
             key: keyed_account.key,
-            state,
+            state: Arc::new(state),
             beneficiary: None,
+            beneficiary_fee: 0,
+            clock_ref: amm_context.clock_ref.clone(),
+            is_vault_active: true,
+            token_programs,
+            health,
         })
     }
 
@@ -65,52 +292,138 @@ impl Amm for WeightedSwap {
     }
 
     fn get_accounts_to_update(&self) -> Vec<Pubkey> {
-        // let vault_authority = get_vault_authority_address(&self.state.vault);
-        // self.state
-        //     .tokens
-        //     .iter()
-        //     .map(|token| get_associated_token_address(&vault_authority, &token.mint))
-        //     .collect()
-
-        vec![self.key, self.state.vault]
+        let mut accounts = vec![self.key, self.state.vault];
+        accounts.extend(self.state.tokens.iter().map(|token| token.mint));
+        accounts.extend(self.get_vault_token_addresses());
+        // keep the first occurrence, so the order stays pool, vault, mints, vault token accounts. A repeated mint
+        // would otherwise list the mint and its vault token account twice
+        let mut seen = HashSet::new();
+        accounts.retain(|account| seen.insert(*account));
+        accounts
     }
 
     fn update(&mut self, account_map: &AccountMap) -> Result<()> {
-        // for address in self.get_accounts_to_update().iter() {
-        //     let buff = try_get_account_data(account_map, &address)?;
-        //     let token_account = TokenAccount::unpack(buff)?;
-        // }
-
+        // read both accounts before touching any state, so a failed update can't pair the vault of one
+        // snapshot with the pool of another
         let mut vault_data = try_get_account_data(account_map, &self.state.vault)?;
         let vault = Vault::try_deserialize(&mut vault_data)?;
-        self.beneficiary = Some(vault.beneficiary);
-
+        // the beneficiary's cut is taken out of the amount out, so a fee of ONE or more leaves nothing to quote
+        if vault.beneficiary_fee >= ONE {
+            return Err(anyhow!(
+                "vault {} beneficiary fee {} is not below {}",
+                self.state.vault,
+                vault.beneficiary_fee,
+                ONE
+            ));
+        }
         let mut pool_data = try_get_account_data(account_map, &self.key)?;
-        self.state = Pool::try_deserialize(&mut pool_data)?;
+        let mut state = Pool::try_deserialize(&mut pool_data)?;
+        let health = PoolHealth::of(&state);
+
+        // the mints and vault token accounts are read into the new snapshot too, and only a fully read
+        // snapshot replaces the current one
+        let epoch = self.clock_ref.epoch.load(Ordering::Relaxed);
+        let mut token_programs = self.token_programs.clone();
+        for (token, token_program) in state.tokens.iter_mut().zip(token_programs.iter_mut()) {
+            if let Some(mint_account) = account_map.get(&token.mint) {
+                *token_program = mint_account.owner;
+            }
+            let mint_data = try_get_account_data(account_map, &token.mint)?;
+            let mint = StateWithExtensions::<Mint>::unpack(mint_data)?;
+            // legacy mints and token-2022 mints without the extension don't charge transfer fees
+            if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
+                let transfer_fee = transfer_fee_config.get_epoch_fee(epoch);
+                token.transfer_fee_bps = Some(u16::from(transfer_fee.transfer_fee_basis_points));
+                token.max_transfer_fee = u64::from(transfer_fee.maximum_fee);
+            }
+        }
+
+        // the vault is shared by all of its pools, so its token accounts only bound the pool balances
+        for (token_index, address) in vault_token_addresses(&state, &token_programs).iter().enumerate() {
+            // token-2022 vault accounts are only derived once the mint owner is known
+            let Ok(token_account_data) = try_get_account_data(account_map, address) else {
+                continue;
+            };
+            let vault_amount = unpack_token_account_amount(token_account_data)?;
+            state.clamp_balance_to_vault(token_index, vault_amount);
+        }
+
+        self.is_vault_active = vault.is_active;
+        self.beneficiary = Some(vault.beneficiary);
+        self.beneficiary_fee = vault.beneficiary_fee;
+        self.health = health;
+        self.state = Arc::new(state);
+        self.token_programs = token_programs;
 
         Ok(())
     }
 
     fn quote(&self, quote_params: &QuoteParams) -> Result<Quote> {
-        let token_in_index = self.state.get_token_index(quote_params.input_mint);
-        let token_out_index = self.state.get_token_index(quote_params.output_mint);
+        if !self.is_active() {
+            return Err(anyhow!("pool {} is not active", self.key));
+        }
+        if self.beneficiary_fee >= ONE {
+            return Err(anyhow!("beneficiary fee {} is not below {}", self.beneficiary_fee, ONE));
+        }
 
-        let amount_in = self.state.calc_rounded_amount(quote_params.amount, token_in_index);
-        let (amount_out, amount_fee) =
-            self.state
-                .get_swap_result(token_in_index, token_out_index, quote_params.amount, 0);
-
-        Ok(Quote {
-            fee_pct: Decimal::from_i128_with_scale(self.state.swap_fee as i128, SCALE),
-            in_amount: amount_in,
-            out_amount: amount_out,
-            fee_amount: amount_fee,
-            fee_mint: quote_params.output_mint,
-            ..Quote::default()
-        })
+        let token_in_index = self
+            .state
+            .get_token_index(quote_params.input_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.input_mint))?;
+        let token_out_index = self
+            .state
+            .get_token_index(quote_params.output_mint)
+            .ok_or_else(|| anyhow!("mint {} not in pool", quote_params.output_mint))?;
+
+        match quote_params.swap_mode {
+            SwapMode::ExactIn => {
+                let amount_in = self.state.calc_rounded_amount(quote_params.amount, token_in_index);
+                // quotes aren't tied to a user, so no staking discount applies
+                let (amount_out, amount_fee) = self
+                    .state
+                    .get_swap_result(token_in_index, token_out_index, amount_in, 0)
+                    .ok_or_else(|| anyhow!("amount in {} exceeds the max in ratio", amount_in))?;
+
+                // the beneficiary takes its cut from the amount out
+                let beneficiary_amount = amount_out.mul_up(self.beneficiary_fee);
+                let amount_out = amount_out - beneficiary_amount;
+                let amount_fee = amount_fee + beneficiary_amount;
+
+                Ok(Quote {
+                    fee_pct: self.state.swap_fee_decimal(),
+                    in_amount: amount_in,
+                    out_amount: amount_out,
+                    fee_amount: amount_fee,
+                    fee_mint: quote_params.output_mint,
+                    ..Quote::default()
+                })
+            }
+            SwapMode::ExactOut => {
+                let amount_out = self.state.calc_rounded_amount(quote_params.amount, token_out_index);
+                // the beneficiary takes its cut from the amount out, so the pool has to pay out more
+                let gross_amount_out = amount_out.div_up(self.beneficiary_fee.complement());
+                let (amount_in, amount_fee) = self
+                    .state
+                    .get_swap_result_exact_out(token_in_index, token_out_index, gross_amount_out, 0)
+                    .ok_or_else(|| anyhow!("exact out amount {} cannot be quoted by this pool", amount_out))?;
+
+                Ok(Quote {
+                    fee_pct: self.state.swap_fee_decimal(),
+                    in_amount: amount_in,
+                    out_amount: amount_out,
+                    fee_amount: amount_fee,
+                    fee_mint: quote_params.input_mint,
+                    ..Quote::default()
+                })
+            }
+        }
     }
 
     fn get_swap_and_account_metas(&self, swap_params: &SwapParams) -> Result<SwapAndAccountMetas> {
+        if !self.is_active() {
+            return Err(anyhow!("pool {} is not active", self.key));
+        }
+
         let SwapParams {
             token_transfer_authority,
             source_token_account,
@@ -120,11 +433,21 @@ impl Amm for WeightedSwap {
             ..
         } = swap_params;
 
+        // the instruction takes both token programs in fixed slots, but each side's vault and beneficiary
+        // accounts are derived under the program that owns its mint, so mixed pools resolve them independently
+        let token_program_in = self.token_program_of(source_mint)?;
+        let token_program_out = self.token_program_of(destination_mint)?;
+
         let vault_authority = get_vault_authority_address(&self.state.vault);
-        let vault_source_token_account = get_associated_token_address(&vault_authority, &source_mint);
-        let vault_destination_token_account = get_associated_token_address(&vault_authority, &destination_mint);
+        let vault_source_token_account =
+            get_associated_token_address_with_program_id(&vault_authority, source_mint, &token_program_in);
+        let vault_destination_token_account =
+            get_associated_token_address_with_program_id(&vault_authority, destination_mint, &token_program_out);
+        let beneficiary = self
+            .beneficiary
+            .ok_or_else(|| anyhow!("vault {} not loaded, call update first", self.state.vault))?;
         let beneficiary_destination_token_account =
-            get_associated_token_address(&self.beneficiary.as_ref().unwrap(), &destination_mint);
+            get_associated_token_address_with_program_id(&beneficiary, destination_mint, &token_program_out);
 
         Ok(SwapAndAccountMetas {
             swap: Swap::TokenSwap, // StabbleWeightedSWap
@@ -144,7 +467,698 @@ impl Amm for WeightedSwap {
         })
     }
 
+    fn supports_exact_out(&self) -> bool {
+        // routers shouldn't ask a paused pool for exact out quotes
+        self.is_active()
+    }
+
     fn clone_amm(&self) -> Box<dyn Amm + Send + Sync> {
         Box::new(self.clone())
     }
+
+    fn is_active(&self) -> bool {
+        self.state.is_active && self.is_vault_active
+    }
+}
+
+/// token account amount for both the spl-token and the token-2022 layout
+fn unpack_token_account_amount(data: &[u8]) -> Result<u64> {
+    Ok(StateWithExtensions::<TokenAccount>::unpack(data)?.base.amount)
+}
+
+fn vault_token_addresses(state: &Pool, token_programs: &[Pubkey]) -> Vec<Pubkey> {
+    let vault_authority = get_vault_authority_address(&state.vault);
+    state
+        .tokens
+        .iter()
+        .zip(token_programs.iter())
+        .map(|(token, token_program)| {
+            get_associated_token_address_with_program_id(&vault_authority, &token.mint, token_program)
+        })
+        .collect()
+}
+
+/// 1 - spot_price / effective_price, with prices as token in per token out and the spot price at ONE scale
+fn price_impact(quote: &Quote, spot_price: u64) -> Decimal {
+    if quote.in_amount == 0 || quote.out_amount == 0 {
+        return Decimal::ZERO;
+    }
+
+    let ratio = (quote.out_amount as u128 * spot_price as u128) / quote.in_amount as u128;
+    let price_impact = (ONE as u128).saturating_sub(ratio);
+
+    Decimal::from_i128_with_scale(price_impact as i128, SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::tests::pool;
+    use anchor_lang::AccountSerialize;
+    use math::weighted_math;
+    use solana_sdk::account::Account;
+    use spl_associated_token_account::get_associated_token_address;
+    use spl_token::solana_program::program_pack::Pack;
+    use spl_token_2022::{
+        extension::{
+            immutable_owner::ImmutableOwner, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        },
+        state::AccountState,
+    };
+
+    fn token_account(amount: u64) -> TokenAccount {
+        TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+    }
+
+    #[test]
+    fn test_unpack_token_account_amount() {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account(1_000_000), &mut data).unwrap();
+        assert_eq!(unpack_token_account_amount(&data).unwrap(), 1_000_000);
+
+        let len = ExtensionType::try_calculate_account_len::<TokenAccount>(&[ExtensionType::ImmutableOwner]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<TokenAccount>::unpack_uninitialized(&mut data).unwrap();
+        state.base = token_account(2_000_000);
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state.init_extension::<ImmutableOwner>(true).unwrap();
+        assert_eq!(unpack_token_account_amount(&data).unwrap(), 2_000_000);
+
+        assert!(unpack_token_account_amount(&data[..TokenAccount::LEN - 1]).is_err());
+    }
+
+    fn weighted_swap() -> WeightedSwap {
+        WeightedSwap {
+            key: Pubkey::new_unique(),
+            state: Arc::new(pool()),
+            beneficiary: Some(Pubkey::new_unique()),
+            beneficiary_fee: 0,
+            clock_ref: ClockRef::default(),
+            is_vault_active: true,
+            token_programs: vec![spl_token::ID; 2],
+            health: PoolHealth::Ok,
+        }
+    }
+
+    #[test]
+    fn test_supports_exact_out() {
+        let mut weighted_swap = weighted_swap();
+        assert!(weighted_swap.supports_exact_out());
+
+        Arc::make_mut(&mut weighted_swap.state).is_active = false;
+        assert!(!weighted_swap.supports_exact_out());
+    }
+
+    fn swap_params(quote_params: &QuoteParams) -> SwapParams<'static, 'static> {
+        SwapParams {
+            in_amount: quote_params.amount,
+            out_amount: 0,
+            source_mint: quote_params.input_mint,
+            destination_mint: quote_params.output_mint,
+            source_token_account: Pubkey::new_unique(),
+            destination_token_account: Pubkey::new_unique(),
+            token_transfer_authority: Pubkey::new_unique(),
+            open_order_address: None,
+            quote_mint_to_referrer: None,
+            jupiter_program_id: &ID,
+            missing_dynamic_accounts_as_default: false,
+        }
+    }
+
+    #[test]
+    fn test_quote_rounded_amount_in() {
+        let mut weighted_swap = weighted_swap();
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[0].decimals = 12;
+        state.tokens[0].scaling_up = false;
+        state.tokens[0].scaling_factor = 1_000;
+        state.tokens[0].transfer_fee_bps = Some(100);
+        state.tokens[0].max_transfer_fee = u64::MAX;
+        // a deep token out makes a single wrapped unit in show up in the amount out
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[0].balance = 1_000_000;
+        state.tokens[1].balance = 1_000_000_000;
+        let quote_params = QuoteParams {
+            amount: 10_101_999,
+            input_mint: weighted_swap.state.tokens[0].mint,
+            output_mint: weighted_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+
+        let quote = weighted_swap.quote(&quote_params).unwrap();
+        assert_eq!(quote.in_amount, 10_101_000);
+        assert_eq!(
+            weighted_swap.state.get_swap_result(0, 1, quote.in_amount, 0),
+            Some((quote.out_amount, quote.fee_amount))
+        );
+        // the transfer fee on the dust that doesn't make it into the pool would push the amount out up
+        let (amount_out, _) = weighted_swap
+            .state
+            .get_swap_result(0, 1, quote_params.amount, 0)
+            .unwrap();
+        assert!(amount_out > quote.out_amount);
+    }
+
+    fn account(data: Vec<u8>, owner: Pubkey) -> Account {
+        Account {
+            lamports: 1,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn vault(beneficiary: Pubkey) -> Vault {
+        Vault {
+            admin: Pubkey::new_unique(),
+            withdraw_authority: Pubkey::new_unique(),
+            withdraw_authority_bump: 255,
+            authority_bump: 255,
+            is_active: true,
+            beneficiary,
+            beneficiary_fee: 0,
+            pending_admin: None,
+        }
+    }
+
+    fn account_map(weighted_swap: &WeightedSwap, vault: &Vault) -> AccountMap {
+        let mut account_map = AccountMap::new();
+
+        let mut vault_data = vec![];
+        vault.try_serialize(&mut vault_data).unwrap();
+        account_map.insert(weighted_swap.state.vault, account(vault_data, stabble_vault::ID));
+
+        let mut pool_data = vec![];
+        weighted_swap.state.try_serialize(&mut pool_data).unwrap();
+        account_map.insert(weighted_swap.key, account(pool_data, ID));
+
+        for token in weighted_swap.state.tokens.iter() {
+            let mut mint_data = vec![0; Mint::LEN];
+            Mint::pack(
+                Mint {
+                    decimals: token.decimals,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+                &mut mint_data,
+            )
+            .unwrap();
+            account_map.insert(token.mint, account(mint_data, spl_token::ID));
+        }
+
+        account_map
+    }
+
+    #[test]
+    fn test_update_beneficiary() {
+        let mut weighted_swap = weighted_swap();
+        weighted_swap.beneficiary = None;
+        let quote_params = QuoteParams {
+            amount: 1_000_000_000,
+            input_mint: weighted_swap.state.tokens[0].mint,
+            output_mint: weighted_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        let swap_params = swap_params(&quote_params);
+
+        // not loaded from the vault yet
+        assert!(weighted_swap.get_swap_and_account_metas(&swap_params).is_err());
+
+        let beneficiary = Pubkey::new_unique();
+        weighted_swap
+            .update(&account_map(&weighted_swap, &vault(beneficiary)))
+            .unwrap();
+        assert_eq!(weighted_swap.beneficiary, Some(beneficiary));
+
+        // a beneficiary rotated on the vault is picked up by the next update
+        let beneficiary = Pubkey::new_unique();
+        weighted_swap
+            .update(&account_map(&weighted_swap, &vault(beneficiary)))
+            .unwrap();
+        let account_metas = weighted_swap
+            .get_swap_and_account_metas(&swap_params)
+            .unwrap()
+            .account_metas;
+        assert_eq!(
+            account_metas[6].pubkey,
+            get_associated_token_address(&beneficiary, &quote_params.output_mint)
+        );
+        // an all-legacy pool still passes both token programs
+        assert_eq!(account_metas[12].pubkey, spl_token::ID);
+        assert_eq!(account_metas[13].pubkey, spl_token_2022::ID);
+
+        // a failed update leaves the previous snapshot intact
+        let mut account_map = account_map(&weighted_swap, &vault(Pubkey::new_unique()));
+        account_map.remove(&weighted_swap.key);
+        assert!(weighted_swap.update(&account_map).is_err());
+        assert_eq!(weighted_swap.beneficiary, Some(beneficiary));
+    }
+
+    #[test]
+    fn test_beneficiary_fee_not_below_one() {
+        let mut weighted_swap = weighted_swap();
+        let mut vault = vault(Pubkey::new_unique());
+        vault.beneficiary_fee = 100_000_000;
+        weighted_swap.update(&account_map(&weighted_swap, &vault)).unwrap();
+        assert_eq!(weighted_swap.beneficiary_fee, 100_000_000);
+
+        // a vault taking the whole amount out is rejected before anything is replaced
+        vault.beneficiary = Pubkey::new_unique();
+        vault.beneficiary_fee = ONE;
+        assert!(weighted_swap.update(&account_map(&weighted_swap, &vault)).is_err());
+        assert_eq!(weighted_swap.beneficiary_fee, 100_000_000);
+        assert_ne!(weighted_swap.beneficiary, Some(vault.beneficiary));
+
+        // nor can the quote divide by a zero complement
+        weighted_swap.beneficiary_fee = ONE;
+        for swap_mode in [SwapMode::ExactIn, SwapMode::ExactOut] {
+            let quote = weighted_swap.quote(&QuoteParams {
+                amount: 1_000_000,
+                input_mint: weighted_swap.state.tokens[0].mint,
+                output_mint: weighted_swap.state.tokens[1].mint,
+                swap_mode,
+            });
+            assert!(quote.is_err());
+        }
+    }
+
+    #[test]
+    fn test_get_accounts_to_update() {
+        let mut weighted_swap = weighted_swap();
+        let accounts = weighted_swap.get_accounts_to_update();
+        assert_eq!(accounts.len(), 6);
+        assert_eq!(accounts[..2], [weighted_swap.key, weighted_swap.state.vault]);
+        assert_eq!(accounts, weighted_swap.get_accounts_to_update());
+
+        // a repeated mint derives the same vault token account
+        let mint = weighted_swap.state.tokens[0].mint;
+        Arc::make_mut(&mut weighted_swap.state).tokens[1].mint = mint;
+        let vault_token_addresses = weighted_swap.get_vault_token_addresses();
+        assert_eq!(vault_token_addresses[0], vault_token_addresses[1]);
+        let accounts = weighted_swap.get_accounts_to_update();
+        assert_eq!(
+            accounts,
+            vec![
+                weighted_swap.key,
+                weighted_swap.state.vault,
+                mint,
+                vault_token_addresses[0]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clone_shares_state() {
+        let mut weighted_swap = weighted_swap();
+        weighted_swap
+            .update(&account_map(&weighted_swap, &vault(Pubkey::new_unique())))
+            .unwrap();
+
+        // clones point at the same pool, token list included, instead of copying it
+        let clones: Vec<WeightedSwap> = (0..1_000).map(|_| weighted_swap.clone()).collect();
+        assert_eq!(Arc::strong_count(&weighted_swap.state), 1_001);
+        for clone in clones.iter() {
+            assert!(Arc::ptr_eq(&clone.state, &weighted_swap.state));
+            assert_eq!(clone.state.tokens.as_ptr(), weighted_swap.state.tokens.as_ptr());
+        }
+        drop(clones);
+        assert_eq!(Arc::strong_count(&weighted_swap.state), 1);
+
+        // an update swaps in a new snapshot and leaves the one other clones hold alone
+        let mut updated = weighted_swap.clone();
+        let mut state = (*weighted_swap.state).clone();
+        state.tokens[0].balance *= 2;
+        let mut account_map = account_map(&weighted_swap, &vault(Pubkey::new_unique()));
+        let mut pool_data = vec![];
+        state.try_serialize(&mut pool_data).unwrap();
+        account_map.insert(weighted_swap.key, account(pool_data, ID));
+        updated.update(&account_map).unwrap();
+        assert!(!Arc::ptr_eq(&updated.state, &weighted_swap.state));
+        assert_eq!(
+            updated.state.tokens[0].balance,
+            2 * weighted_swap.state.tokens[0].balance
+        );
+        assert_eq!(Arc::strong_count(&weighted_swap.state), 1);
+    }
+
+    #[test]
+    fn test_mixed_token_programs() {
+        let mut weighted_swap = weighted_swap();
+        let mut account_map = account_map(&weighted_swap, &vault(Pubkey::new_unique()));
+        account_map.get_mut(&weighted_swap.state.tokens[1].mint).unwrap().owner = spl_token_2022::ID;
+        weighted_swap.update(&account_map).unwrap();
+        assert_eq!(weighted_swap.token_programs, vec![spl_token::ID, spl_token_2022::ID]);
+
+        let beneficiary = weighted_swap.beneficiary.unwrap();
+        let vault_authority = get_vault_authority_address(&weighted_swap.state.vault);
+        let mints: Vec<Pubkey> = weighted_swap.state.tokens.iter().map(|token| token.mint).collect();
+        for (token_in_index, token_out_index) in [(0, 1), (1, 0)] {
+            let quote_params = QuoteParams {
+                amount: 1_000_000_000,
+                input_mint: mints[token_in_index],
+                output_mint: mints[token_out_index],
+                swap_mode: SwapMode::ExactIn,
+            };
+            let token_program_in = weighted_swap.token_programs[token_in_index];
+            let token_program_out = weighted_swap.token_programs[token_out_index];
+            let account_metas = weighted_swap
+                .get_swap_and_account_metas(&swap_params(&quote_params))
+                .unwrap()
+                .account_metas;
+
+            assert_eq!(
+                account_metas[4].pubkey,
+                get_associated_token_address_with_program_id(
+                    &vault_authority,
+                    &mints[token_in_index],
+                    &token_program_in
+                )
+            );
+            assert_eq!(
+                account_metas[5].pubkey,
+                get_associated_token_address_with_program_id(
+                    &vault_authority,
+                    &mints[token_out_index],
+                    &token_program_out
+                )
+            );
+            assert_eq!(
+                account_metas[6].pubkey,
+                get_associated_token_address_with_program_id(&beneficiary, &mints[token_out_index], &token_program_out)
+            );
+            assert_eq!(account_metas.len(), 14);
+            assert_eq!(account_metas[12].pubkey, spl_token::ID);
+            assert_eq!(account_metas[13].pubkey, spl_token_2022::ID);
+        }
+
+        // a mint missing halfway through fails the update without touching the previous snapshot
+        let previous_state = Arc::clone(&weighted_swap.state);
+        account_map.get_mut(&mints[0]).unwrap().owner = spl_token_2022::ID;
+        account_map.remove(&mints[1]);
+        assert!(weighted_swap.update(&account_map).is_err());
+        assert!(Arc::ptr_eq(&weighted_swap.state, &previous_state));
+        assert_eq!(weighted_swap.token_programs, vec![spl_token::ID, spl_token_2022::ID]);
+        assert_eq!(weighted_swap.beneficiary, Some(beneficiary));
+
+        // mints outside the pool have no token program to resolve
+        let quote_params = QuoteParams {
+            amount: 1_000_000_000,
+            input_mint: mints[0],
+            output_mint: Pubkey::new_unique(),
+            swap_mode: SwapMode::ExactIn,
+        };
+        assert!(weighted_swap.token_program_of(&quote_params.output_mint).is_err());
+        assert!(weighted_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_err());
+    }
+
+    #[test]
+    fn test_tvl_in_token() {
+        let mut weighted_swap = weighted_swap();
+        let mints: Vec<Pubkey> = weighted_swap.state.tokens.iter().map(|token| token.mint).collect();
+        let balance = weighted_swap.state.tokens[0].balance;
+
+        assert_eq!(weighted_swap.tvl_in_token(&mints[0]), Some(2 * balance));
+        assert_eq!(weighted_swap.tvl_in_token(&mints[1]), Some(2 * balance));
+        assert_eq!(weighted_swap.tvl_in_token(&Pubkey::new_unique()), None);
+
+        // a 50/50 pool holds equal value on both sides at its spot price
+        Arc::make_mut(&mut weighted_swap.state).tokens[1].balance = 4 * balance;
+        assert_eq!(weighted_swap.tvl_in_token(&mints[0]), Some(2 * balance));
+        assert_eq!(weighted_swap.tvl_in_token(&mints[1]), Some(8 * balance));
+
+        // an 80/20 pool holds four times the value on the heavier side
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[0].weight = 800_000_000;
+        state.tokens[1].weight = 200_000_000;
+        state.tokens[1].balance = balance;
+        assert_eq!(weighted_swap.tvl_in_token(&mints[0]), Some(balance + balance / 4));
+        assert_eq!(weighted_swap.tvl_in_token(&mints[1]), Some(5 * balance));
+
+        // reserves are valued in token amounts, not wrapped balances
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        assert_eq!(weighted_swap.tvl_in_token(&mints[0]), Some(balance + balance / 4));
+        assert_eq!(weighted_swap.tvl_in_token(&mints[1]), Some(5 * balance / 1_000));
+    }
+
+    #[test]
+    fn test_reserve_of() {
+        let mut weighted_swap = weighted_swap();
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        let mints: Vec<Pubkey> = weighted_swap.state.tokens.iter().map(|token| token.mint).collect();
+
+        assert_eq!(
+            weighted_swap.reserve_of(&mints[0]),
+            Some(weighted_swap.state.tokens[0].balance)
+        );
+        assert_eq!(
+            weighted_swap.reserve_of(&mints[1]),
+            Some(weighted_swap.state.tokens[1].balance / 1_000)
+        );
+        assert_eq!(
+            mints
+                .iter()
+                .map(|mint| weighted_swap.reserve_of(mint).unwrap())
+                .collect::<Vec<_>>(),
+            weighted_swap.state.get_reserves()
+        );
+        assert_eq!(weighted_swap.reserve_of(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn test_inactive() {
+        let mut weighted_swap = weighted_swap();
+        let quote_params = QuoteParams {
+            amount: 1_000_000_000,
+            input_mint: weighted_swap.state.tokens[0].mint,
+            output_mint: weighted_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+        assert!(weighted_swap.is_active());
+        assert!(weighted_swap.quote(&quote_params).is_ok());
+        assert!(weighted_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_ok());
+
+        weighted_swap.is_vault_active = false;
+        assert!(!weighted_swap.is_active());
+        assert!(weighted_swap.quote(&quote_params).is_err());
+        assert!(weighted_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_err());
+
+        weighted_swap.is_vault_active = true;
+        Arc::make_mut(&mut weighted_swap.state).is_active = false;
+        assert!(!weighted_swap.is_active());
+        assert!(weighted_swap.quote(&quote_params).is_err());
+        assert!(weighted_swap
+            .get_swap_and_account_metas(&swap_params(&quote_params))
+            .is_err());
+    }
+
+    #[test]
+    fn test_from_keyed_account_unchecked() {
+        let amm_context = AmmContext {
+            clock_ref: ClockRef::default(),
+        };
+        let keyed_account = |state: &Pool| {
+            let mut data = vec![];
+            state.try_serialize(&mut data).unwrap();
+            KeyedAccount {
+                key: Pubkey::new_unique(),
+                account: account(data, ID),
+                params: None,
+            }
+        };
+
+        let state = pool();
+        let loaded = WeightedSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::Ok);
+        assert_eq!(*loaded.state, state);
+        assert_eq!(
+            WeightedSwap::from_keyed_account(&keyed_account(&state), &amm_context)
+                .unwrap()
+                .health(),
+            PoolHealth::Ok
+        );
+
+        let mut state = pool();
+        state.is_active = false;
+        let loaded = WeightedSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::Inactive);
+        assert_eq!(*loaded.state, state);
+        assert_eq!(
+            WeightedSwap::from_keyed_account(&keyed_account(&state), &amm_context)
+                .unwrap()
+                .health(),
+            PoolHealth::Inactive
+        );
+
+        // malformed pools only load through the lenient constructor
+        let mut state = pool();
+        state.tokens[0].weight = 0;
+        assert!(WeightedSwap::from_keyed_account(&keyed_account(&state), &amm_context).is_err());
+        let loaded = WeightedSwap::from_keyed_account_unchecked(&keyed_account(&state), &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::DeserializeFailed);
+        assert!(!loaded.is_active());
+        assert!(loaded.get_reserve_mints().is_empty());
+
+        let mut keyed_account = keyed_account(&pool());
+        keyed_account.account.data.truncate(16);
+        assert!(WeightedSwap::from_keyed_account(&keyed_account, &amm_context).is_err());
+        let loaded = WeightedSwap::from_keyed_account_unchecked(&keyed_account, &amm_context);
+        assert_eq!(loaded.health(), PoolHealth::DeserializeFailed);
+
+        // the pool can be deactivated after it was loaded
+        let mut weighted_swap = weighted_swap();
+        let mut deactivated = weighted_swap.clone();
+        Arc::make_mut(&mut deactivated.state).is_active = false;
+        weighted_swap
+            .update(&account_map(&deactivated, &vault(Pubkey::new_unique())))
+            .unwrap();
+        assert_eq!(weighted_swap.health(), PoolHealth::Inactive);
+    }
+
+    #[test]
+    fn test_max_amount_in_out() {
+        let quote = |weighted_swap: &WeightedSwap, amount: u64, swap_mode: SwapMode| {
+            weighted_swap.quote(&QuoteParams {
+                amount,
+                input_mint: weighted_swap.state.tokens[0].mint,
+                output_mint: weighted_swap.state.tokens[1].mint,
+                swap_mode,
+            })
+        };
+
+        let mut weighted_swap = weighted_swap();
+        let in_mint = weighted_swap.state.tokens[0].mint;
+        let out_mint = weighted_swap.state.tokens[1].mint;
+        assert_eq!(weighted_swap.max_amount_in(&in_mint, &Pubkey::new_unique()), None);
+        assert_eq!(weighted_swap.max_amount_out(&Pubkey::new_unique(), &out_mint), None);
+
+        let max_amount_in = weighted_swap.max_amount_in(&in_mint, &out_mint).unwrap();
+        assert_eq!(
+            max_amount_in,
+            weighted_swap.state.tokens[0]
+                .balance
+                .mul_down(weighted_math::MAX_IN_RATIO)
+        );
+        assert!(quote(&weighted_swap, max_amount_in, SwapMode::ExactIn).is_ok());
+        assert!(quote(&weighted_swap, max_amount_in + 1, SwapMode::ExactIn).is_err());
+
+        let max_amount_out = weighted_swap.max_amount_out(&in_mint, &out_mint).unwrap();
+        assert_eq!(
+            max_amount_out,
+            weighted_swap.state.tokens[1]
+                .balance
+                .mul_down(weighted_math::MAX_OUT_RATIO)
+        );
+        assert!(quote(&weighted_swap, max_amount_out, SwapMode::ExactOut).is_ok());
+        assert!(quote(&weighted_swap, max_amount_out + 1, SwapMode::ExactOut).is_err());
+
+        // transfer fees and the beneficiary's cut move the limits, the pool side stays the same
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[0].transfer_fee_bps = Some(100);
+        state.tokens[0].max_transfer_fee = u64::MAX;
+        state.tokens[1].transfer_fee_bps = Some(100);
+        state.tokens[1].max_transfer_fee = u64::MAX;
+        weighted_swap.beneficiary_fee = 100_000_000;
+
+        let max_amount_in_with_fees = weighted_swap.max_amount_in(&in_mint, &out_mint).unwrap();
+        assert!(max_amount_in_with_fees > max_amount_in);
+        assert!(quote(&weighted_swap, max_amount_in_with_fees, SwapMode::ExactIn).is_ok());
+
+        let max_amount_out_with_fees = weighted_swap.max_amount_out(&in_mint, &out_mint).unwrap();
+        assert!(max_amount_out_with_fees < max_amount_out);
+        assert!(quote(&weighted_swap, max_amount_out_with_fees, SwapMode::ExactOut).is_ok());
+        assert!(quote(&weighted_swap, max_amount_out_with_fees + 1, SwapMode::ExactOut).is_err());
+    }
+
+    #[test]
+    fn test_detailed_quote() {
+        let mut weighted_swap = weighted_swap();
+        let state = Arc::make_mut(&mut weighted_swap.state);
+        state.tokens[1].decimals = 6;
+        state.tokens[1].scaling_factor = 1_000;
+        let mut quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: weighted_swap.state.tokens[0].mint,
+            output_mint: weighted_swap.state.tokens[1].mint,
+            swap_mode: SwapMode::ExactIn,
+        };
+
+        let quote = weighted_swap.quote(&quote_params).unwrap();
+        let detailed_quote = weighted_swap.detailed_quote(&quote_params).unwrap();
+        assert_eq!(detailed_quote.amount_in, Decimal::new(quote.in_amount as i64, 9));
+        assert_eq!(detailed_quote.amount_out, Decimal::new(quote.out_amount as i64, 6));
+        assert_eq!(detailed_quote.fee, Decimal::new(quote.fee_amount as i64, 6));
+        // equal wrapped balances are worth the same in whole tokens whatever the decimals
+        assert_eq!(detailed_quote.spot_price, Decimal::ONE);
+        assert_eq!(
+            detailed_quote.effective_price,
+            detailed_quote.amount_in / detailed_quote.amount_out
+        );
+        assert!(detailed_quote.effective_price > detailed_quote.spot_price);
+        assert_eq!(
+            Some(detailed_quote.price_impact),
+            weighted_swap.price_impact_pct(&quote_params)
+        );
+
+        // the other way round the price is quoted in the 6 decimal token
+        quote_params.input_mint = weighted_swap.state.tokens[1].mint;
+        quote_params.output_mint = weighted_swap.state.tokens[0].mint;
+        quote_params.amount = 1_000;
+        let quote = weighted_swap.quote(&quote_params).unwrap();
+        let detailed_quote = weighted_swap.detailed_quote(&quote_params).unwrap();
+        assert_eq!(detailed_quote.amount_in, Decimal::new(quote.in_amount as i64, 6));
+        assert_eq!(detailed_quote.amount_out, Decimal::new(quote.out_amount as i64, 9));
+        assert_eq!(detailed_quote.spot_price, Decimal::ONE);
+
+        quote_params.output_mint = Pubkey::new_unique();
+        assert!(weighted_swap.detailed_quote(&quote_params).is_err());
+    }
+
+    #[test]
+    fn test_zero_amount_quote() {
+        let weighted_swap = weighted_swap();
+
+        let quote = weighted_swap
+            .quote(&QuoteParams {
+                amount: 0,
+                input_mint: weighted_swap.state.tokens[0].mint,
+                output_mint: weighted_swap.state.tokens[1].mint,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .unwrap();
+        assert_eq!((quote.in_amount, quote.out_amount, quote.fee_amount), (0, 0, 0));
+
+        let quote = weighted_swap
+            .quote(&QuoteParams {
+                amount: 0,
+                input_mint: weighted_swap.state.tokens[0].mint,
+                output_mint: weighted_swap.state.tokens[1].mint,
+                swap_mode: SwapMode::ExactOut,
+            })
+            .unwrap();
+        assert_eq!((quote.in_amount, quote.out_amount, quote.fee_amount), (0, 0, 0));
+
+        // without touching the weighted math, which can't pay out of an empty balance
+        let mut state = pool();
+        state.tokens[1].balance = 0;
+        assert_eq!(state.get_swap_result_exact_out(0, 1, 0, 0), Some((0, 0)));
+        assert_eq!(state.get_swap_result_exact_out(0, 1, 1_000, 0), None);
+    }
 }
@@ -1,49 +1,158 @@
 use anchor_lang::prelude::borsh;
 use anchor_lang::{account, solana_program::pubkey::Pubkey, AnchorDeserialize, AnchorSerialize};
+use bn::{safe_math::CheckedDivCeil, uint192, U192};
 use math::{
-    fixed_math::{FixedComplement, FixedMul},
+    base_pool_math,
+    error::{PoolError, ScalingError, WeightedMathError},
+    fixed_math::{FixedComplement, FixedDiv, FixedMul, ONE, SCALE},
     swap_fee_math, weighted_math,
 };
+use rust_decimal::Decimal;
+#[cfg(feature = "serde")]
+use serde_with::{As, DisplayFromStr};
 
-#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy)]
+/// transfer fee basis points of a token-2022 mint that withholds the whole amount
+pub const MAX_FEE_BASIS_POINTS: u16 = 10_000;
+
+/// largest power of ten a scaling factor can be, 10^19 is the last one that fits u64
+pub const MAX_SCALING_EXPONENT: u32 = 19;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(AnchorSerialize, AnchorDeserialize, Eq, PartialEq, Clone, Copy, Debug)]
 pub struct PoolToken {
-    pub mint: Pubkey,        // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub mint: Pubkey, // immutable
     pub decimals: u8,        // immutable
     pub scaling_up: bool,    // immutable
     pub scaling_factor: u64, // immutable
     pub balance: u64,
     pub weight: u64, // immutable
+    /// token-2022 transfer fee basis points for the current epoch, read from the mint off-chain
+    #[borsh_skip]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub transfer_fee_bps: Option<u16>,
+    /// token-2022 maximum transfer fee for the current epoch, read from the mint off-chain
+    #[borsh_skip]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_transfer_fee: u64,
 }
 
 #[account]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Eq, PartialEq, Debug)]
 pub struct Pool {
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
     pub owner: Pubkey,
-    pub vault: Pubkey,      // immutable
-    pub mint: Pubkey,       // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub vault: Pubkey, // immutable
+    #[cfg_attr(feature = "serde", serde(with = "As::<DisplayFromStr>"))]
+    pub mint: Pubkey, // immutable
     pub authority_bump: u8, // immutable
     pub is_active: bool,
     pub invariant: u64,
     pub swap_fee: u64,
     pub tokens: Vec<PoolToken>,
+    #[cfg_attr(feature = "serde", serde(with = "As::<Option<DisplayFromStr>>"))]
     pub pending_owner: Option<Pubkey>,
 }
 
 impl Pool {
+    /// active pool over `tokens` for tests and simulations, the on-chain path is `try_deserialize`. The owner, mint
+    /// and authority bump are left at their defaults since quoting never reads them
+    pub fn new(vault: Pubkey, swap_fee: u64, tokens: Vec<PoolToken>) -> Self {
+        let mut pool = Self {
+            owner: Pubkey::default(),
+            vault,
+            mint: Pubkey::default(),
+            authority_bump: 0,
+            is_active: true,
+            invariant: 0,
+            swap_fee,
+            tokens,
+            pending_owner: None,
+        };
+        // an empty pool keeps a zero invariant, which quotes nothing
+        pool.invariant =
+            weighted_math::calc_invariant_checked(&pool.get_balances(), &pool.get_normalized_weights()).unwrap_or(0);
+        pool
+    }
+
     pub fn get_normalized_weights(&self) -> Vec<u64> {
         self.tokens.iter().map(|token| token.weight).collect()
     }
 
+    /// checks each weight is within the supported range and all of them sum to ONE
+    pub fn validate_weights(&self) -> Result<(), WeightedMathError> {
+        let mut weight_sum = 0u64;
+        for token in self.tokens.iter() {
+            if token.weight < weighted_math::MIN_WEIGHT || token.weight > weighted_math::MAX_WEIGHT {
+                return Err(WeightedMathError::InvalidWeights);
+            }
+            weight_sum += token.weight;
+        }
+        if weight_sum.abs_diff(ONE) > weighted_math::WEIGHT_SUM_TOLERANCE {
+            return Err(WeightedMathError::InvalidWeights);
+        }
+
+        Ok(())
+    }
+
+    /// checks every invariant a deserialized pool has to hold: token count, swap fee, weights, scaling factors and
+    /// unique mints
+    pub fn validate(&self) -> Result<(), PoolError> {
+        if !(weighted_math::MIN_TOKENS..=weighted_math::MAX_TOKENS).contains(&self.tokens.len()) {
+            return Err(PoolError::InvalidTokenCount);
+        }
+        if !(weighted_math::MIN_SWAP_FEE..=weighted_math::MAX_SWAP_FEE).contains(&self.swap_fee) {
+            return Err(PoolError::InvalidSwapFee);
+        }
+        self.validate_weights().map_err(|_| PoolError::InvalidWeights)?;
+        self.validate_scaling()?;
+        // pools hold a handful of tokens, so a pairwise scan is cheaper than hashing
+        for (token_index, token) in self.tokens.iter().enumerate() {
+            if self.tokens[..token_index].iter().any(|other| other.mint == token.mint) {
+                return Err(PoolError::DuplicateMint);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// checks each scaling factor is a power of ten, the only decimal scales the wrapping supports
+    pub fn validate_scaling(&self) -> Result<(), ScalingError> {
+        for token in self.tokens.iter() {
+            if !(0..=MAX_SCALING_EXPONENT).any(|exponent| token.scaling_factor == 10u64.pow(exponent)) {
+                return Err(ScalingError::InvalidScalingFactor);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// swap fee as a fraction, e.g. 1_000_000 is 0.001 (0.1%), which is what Jupiter expects in `fee_pct`
+    pub fn swap_fee_decimal(&self) -> Decimal {
+        Decimal::from_i128_with_scale(self.swap_fee as i128, SCALE)
+    }
+
     pub fn get_balances(&self) -> Vec<u64> {
         self.tokens.iter().map(|token| token.balance).collect()
     }
 
-    pub fn get_token_index(&self, mint: Pubkey) -> usize {
-        self.tokens
-            .iter()
-            .enumerate()
-            .find(|(_, token)| token.mint == mint)
-            .unwrap()
-            .0
+    /// balances unwrapped to token amounts
+    pub fn get_reserves(&self) -> Vec<u64> {
+        (0..self.tokens.len())
+            .map(|token_index| self.calc_unwrapped_amount(self.tokens[token_index].balance, token_index))
+            .collect()
+    }
+
+    pub fn get_token_index(&self, mint: Pubkey) -> Option<usize> {
+        self.tokens.iter().position(|token| token.mint == mint)
+    }
+
+    /// decimals of `mint`, `None` if the mint isn't in the pool
+    pub fn decimals_of(&self, mint: &Pubkey) -> Option<u8> {
+        let token_index = self.get_token_index(*mint)?;
+        Some(self.tokens[token_index].decimals)
     }
 
     /// scaling up/down from token amount to wrapped balance amount
@@ -68,6 +177,41 @@ impl Pool {
         }
     }
 
+    /// token amounts in for minting exactly `bpt_out` pool tokens, returns `None` for an empty pool
+    pub fn quote_add_liquidity_proportional(&self, bpt_out: u64, pool_token_supply: u64) -> Option<Vec<u64>> {
+        let wrapped_amounts_in =
+            base_pool_math::compute_proportional_amounts_in(&self.get_balances(), pool_token_supply, bpt_out)?;
+
+        // unwrapping rounds up as well, so the user never pays less than the pool tokens are worth
+        wrapped_amounts_in
+            .iter()
+            .enumerate()
+            .map(|(token_index, wrapped_amount_in)| {
+                let token = &self.tokens[token_index];
+                if token.scaling_factor == 1 || !token.scaling_up {
+                    Some(self.calc_unwrapped_amount(*wrapped_amount_in, token_index))
+                } else {
+                    wrapped_amount_in.checked_div_up(token.scaling_factor)
+                }
+            })
+            .collect()
+    }
+
+    /// token amounts out for burning `bpt_in` pool tokens across all tokens, returns `None` for an empty pool
+    pub fn quote_remove_liquidity_proportional(&self, bpt_in: u64, pool_token_supply: u64) -> Option<Vec<u64>> {
+        let wrapped_amounts_out =
+            base_pool_math::compute_proportional_amounts_out(&self.get_balances(), pool_token_supply, bpt_in)?;
+
+        // unwrapping rounds down as well, so the pool never pays out more than the pool tokens are worth
+        Some(
+            wrapped_amounts_out
+                .iter()
+                .enumerate()
+                .map(|(token_index, wrapped_amount_out)| self.calc_unwrapped_amount(*wrapped_amount_out, token_index))
+                .collect(),
+        )
+    }
+
     /// round down token amount not to send the lost amount from wrapped balance amount when it scaled down
     pub fn calc_rounded_amount(&self, amount: u64, token_index: usize) -> u64 {
         if self.tokens[token_index].scaling_up {
@@ -77,20 +221,164 @@ impl Pool {
         }
     }
 
-    /// estimated swap amount out
+    /// caps the wrapped balance at the vault token account amount, since the vault can't pay out more than it holds
+    pub fn clamp_balance_to_vault(&mut self, token_index: usize, vault_amount: u64) {
+        let balance = self.tokens[token_index].balance;
+        if self.calc_unwrapped_amount(balance, token_index) > vault_amount {
+            self.tokens[token_index].balance = self.calc_wrapped_amount(vault_amount, token_index);
+        }
+    }
+
+    /// token-2022 transfer fee withheld from a transfer of the token amount, zero without the extension
+    pub fn calc_transfer_fee(&self, amount: u64, token_index: usize) -> u64 {
+        let token = &self.tokens[token_index];
+        match token.transfer_fee_bps {
+            Some(transfer_fee_bps) => {
+                let fee = (amount as u128 * transfer_fee_bps as u128).div_ceil(MAX_FEE_BASIS_POINTS as u128);
+                (fee as u64).min(token.max_transfer_fee)
+            }
+            None => 0,
+        }
+    }
+
+    /// token amount to transfer so that the given amount arrives after the token-2022 transfer fee
+    pub fn calc_pre_transfer_fee_amount(&self, amount: u64, token_index: usize) -> Option<u64> {
+        let token = &self.tokens[token_index];
+        match token.transfer_fee_bps {
+            Some(_) if amount == 0 => Some(0),
+            Some(0) | None => Some(amount),
+            Some(MAX_FEE_BASIS_POINTS) => amount.checked_add(token.max_transfer_fee),
+            Some(transfer_fee_bps) => {
+                let pre_fee_amount = (amount as u128 * MAX_FEE_BASIS_POINTS as u128)
+                    .div_ceil((MAX_FEE_BASIS_POINTS - transfer_fee_bps) as u128);
+                if pre_fee_amount - amount as u128 >= token.max_transfer_fee as u128 {
+                    amount.checked_add(token.max_transfer_fee)
+                } else {
+                    u64::try_from(pre_fee_amount).ok()
+                }
+            }
+        }
+    }
+
+    /// largest token amount in that stays within the max in ratio once the transfer fee is taken
+    pub fn get_max_amount_in(&self, token_in_index: usize) -> Option<u64> {
+        let wrapped_max_amount_in = self.tokens[token_in_index]
+            .balance
+            .mul_down(weighted_math::MAX_IN_RATIO);
+        let max_amount_in = self.calc_unwrapped_amount(wrapped_max_amount_in, token_in_index);
+        self.calc_pre_transfer_fee_amount(max_amount_in, token_in_index)
+    }
+
+    /// largest exact token amount out that stays within the max out ratio, net of the transfer fee
+    pub fn get_max_amount_out(&self, token_out_index: usize) -> u64 {
+        let wrapped_max_amount_out = self.tokens[token_out_index]
+            .balance
+            .mul_down(weighted_math::MAX_OUT_RATIO);
+        let max_amount_out = self.calc_unwrapped_amount(wrapped_max_amount_out, token_out_index);
+        max_amount_out - self.calc_transfer_fee(max_amount_out, token_out_index)
+    }
+
+    /// spot price of token out in terms of token in, in unwrapped token amounts at ONE scale
+    pub fn get_spot_price(&self, token_in_index: usize, token_out_index: usize) -> Option<u64> {
+        let token_in = &self.tokens[token_in_index];
+        let token_out = &self.tokens[token_out_index];
+
+        // (balance_in / weight_in) / (balance_out / weight_out)
+        let mut numerator = uint192!(token_in.balance)
+            .checked_mul(uint192!(token_out.weight))?
+            .checked_mul(uint192!(ONE))?;
+        let mut denominator = uint192!(token_out.balance).checked_mul(uint192!(token_in.weight))?;
+
+        // balances are wrapped, so scale them back to token amounts
+        if token_in.scaling_up {
+            denominator = denominator.checked_mul(uint192!(token_in.scaling_factor))?;
+        } else {
+            numerator = numerator.checked_mul(uint192!(token_in.scaling_factor))?;
+        }
+        if token_out.scaling_up {
+            numerator = numerator.checked_mul(uint192!(token_out.scaling_factor))?;
+        } else {
+            denominator = denominator.checked_mul(uint192!(token_out.scaling_factor))?;
+        }
+
+        let price = numerator.checked_div(denominator)?;
+        if price > uint192!(u64::MAX) {
+            None
+        } else {
+            Some(price.as_u64())
+        }
+    }
+
+    /// estimated pool token amount out for the given token amounts in
+    pub fn quote_add_liquidity(&self, amounts_in: &[u64], pool_token_supply: u64) -> Option<u64> {
+        if amounts_in.len() != self.tokens.len() {
+            return None;
+        }
+
+        let wrapped_amounts_in = amounts_in
+            .iter()
+            .enumerate()
+            .map(|(token_index, amount)| self.calc_wrapped_amount(*amount, token_index))
+            .collect();
+
+        weighted_math::calc_pool_token_out_given_exact_tokens_in(
+            &self.get_balances(),
+            &self.get_normalized_weights(),
+            &wrapped_amounts_in,
+            pool_token_supply,
+            self.swap_fee,
+        )
+        .ok()
+    }
+
+    /// estimated token amount out and fee for burning pool tokens, returns `None` below the min invariant ratio
+    pub fn quote_remove_liquidity_single(
+        &self,
+        token_index: usize,
+        bpt_in: u64,
+        pool_token_supply: u64,
+    ) -> Option<(u64, u64)> {
+        let token = &self.tokens[token_index];
+        let wrapped_amount_out = weighted_math::calc_token_out_given_exact_pool_token_in(
+            token.balance,
+            token.weight,
+            bpt_in,
+            pool_token_supply,
+            self.swap_fee,
+        )
+        .ok()?;
+        let wrapped_amount_out_without_fee = weighted_math::calc_token_out_given_exact_pool_token_in(
+            token.balance,
+            token.weight,
+            bpt_in,
+            pool_token_supply,
+            0,
+        )
+        .ok()?;
+
+        let wrapped_amount_fee = wrapped_amount_out_without_fee - wrapped_amount_out;
+        let amount_out = self.calc_unwrapped_amount(wrapped_amount_out, token_index);
+        let amount_fee = self.calc_unwrapped_amount(wrapped_amount_fee, token_index);
+
+        Some((amount_out, amount_fee))
+    }
+
+    /// estimated swap amount out, returns `None` if the amount in exceeds the max in ratio
     pub fn get_swap_result(
         &self,
         token_in_index: usize,
         token_out_index: usize,
         amount_in: u64,
         x_amount: u64,
-    ) -> (u64, u64) {
-        if self.invariant == 0 {
-            return (0, 0);
+    ) -> Option<(u64, u64)> {
+        if self.invariant == 0 || amount_in == 0 {
+            return Some((0, 0));
         }
 
         let swap_fee = swap_fee_math::calc_swap_fee_in_discount(self.swap_fee, x_amount);
 
+        // the pool is credited with what is left after the transfer fee on the way in
+        let amount_in = amount_in - self.calc_transfer_fee(amount_in, token_in_index);
         let wrapped_amount_in = self.calc_wrapped_amount(amount_in, token_in_index);
         let wrapped_amount_out_without_fee = weighted_math::calc_out_given_in(
             self.tokens[token_in_index].balance,
@@ -99,13 +387,767 @@ impl Pool {
             self.tokens[token_out_index].weight,
             wrapped_amount_in,
         )
-        .unwrap();
+        .ok()?;
 
         let wrapped_amount_out = wrapped_amount_out_without_fee.mul_down(swap_fee.complement());
         let wrapped_amount_fee = wrapped_amount_out_without_fee - wrapped_amount_out;
         let amount_out = self.calc_unwrapped_amount(wrapped_amount_out, token_out_index);
+        let amount_out = amount_out - self.calc_transfer_fee(amount_out, token_out_index);
         let amount_fee = self.calc_unwrapped_amount(wrapped_amount_fee, token_out_index);
 
-        (amount_out, amount_fee)
+        Some((amount_out, amount_fee))
+    }
+
+    /// estimated swap amount in for an exact amount out, returns `None` if the amount out exceeds the max out ratio
+    pub fn get_swap_result_exact_out(
+        &self,
+        token_in_index: usize,
+        token_out_index: usize,
+        amount_out: u64,
+        x_amount: u64,
+    ) -> Option<(u64, u64)> {
+        if self.invariant == 0 {
+            return None;
+        }
+        if amount_out == 0 {
+            return Some((0, 0));
+        }
+
+        let swap_fee = swap_fee_math::calc_swap_fee_in_discount(self.swap_fee, x_amount);
+
+        // the pool has to send more for the exact amount to arrive after the transfer fee
+        let amount_out = self.calc_pre_transfer_fee_amount(amount_out, token_out_index)?;
+        let wrapped_amount_out = self.calc_wrapped_amount(amount_out, token_out_index);
+        let wrapped_amount_in_without_fee = weighted_math::calc_in_given_out(
+            self.tokens[token_in_index].balance,
+            self.tokens[token_in_index].weight,
+            self.tokens[token_out_index].balance,
+            self.tokens[token_out_index].weight,
+            wrapped_amount_out,
+        )
+        .ok()?;
+
+        // the fee is taken from the amount in, so gross it up and round against the user
+        let wrapped_amount_in = wrapped_amount_in_without_fee.div_up(swap_fee.complement());
+        let wrapped_amount_fee = wrapped_amount_in - wrapped_amount_in_without_fee;
+        let token_in = &self.tokens[token_in_index];
+        let (amount_in, amount_fee) = if token_in.scaling_factor == 1 || !token_in.scaling_up {
+            (
+                self.calc_unwrapped_amount(wrapped_amount_in, token_in_index),
+                self.calc_unwrapped_amount(wrapped_amount_fee, token_in_index),
+            )
+        } else {
+            (
+                wrapped_amount_in.checked_div_up(token_in.scaling_factor)?,
+                wrapped_amount_fee.checked_div_up(token_in.scaling_factor)?,
+            )
+        };
+        let amount_in = self.calc_pre_transfer_fee_amount(amount_in, token_in_index)?;
+
+        Some((amount_in, amount_fee))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use anchor_lang::{error::ErrorCode, AccountDeserialize, AccountSerialize, Discriminator};
+    use std::str::FromStr;
+
+    const BALANCE: u64 = 1_000_000_000_000;
+
+    pub(crate) fn pool() -> Pool {
+        let token = PoolToken {
+            mint: Pubkey::new_unique(),
+            decimals: 9,
+            scaling_up: true,
+            scaling_factor: 1,
+            balance: BALANCE,
+            weight: 500_000_000,
+            transfer_fee_bps: None,
+            max_transfer_fee: 0,
+        };
+        Pool {
+            owner: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            authority_bump: 255,
+            is_active: true,
+            invariant: 1_000_000_000_000,
+            swap_fee: 1_000_000,
+            tokens: vec![
+                token,
+                PoolToken {
+                    mint: Pubkey::new_unique(),
+                    ..token
+                },
+            ],
+            pending_owner: None,
+        }
+    }
+
+    fn pool_data() -> Vec<u8> {
+        let mut data = vec![];
+        pool().try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_new() {
+        let vault = Pubkey::new_unique();
+        let tokens = pool().tokens;
+        let new_pool = Pool::new(vault, 1_000_000, tokens.clone());
+        assert_eq!(new_pool.vault, vault);
+        assert_eq!(new_pool.tokens, tokens);
+        assert!(new_pool.is_active);
+        assert_eq!(new_pool.invariant, 999_999_999_956);
+        assert_eq!(new_pool.validate_weights(), Ok(()));
+        assert_eq!(
+            new_pool.get_swap_result(0, 1, 1_000_000_000, 0),
+            Pool { tokens, ..pool() }.get_swap_result(0, 1, 1_000_000_000, 0)
+        );
+
+        let mut tokens = pool().tokens;
+        tokens[0].balance = 0;
+        assert_eq!(Pool::new(vault, 1_000_000, tokens).invariant, 0);
+    }
+
+    #[test]
+    fn test_get_spot_price() {
+        let mut pool = pool();
+        pool.tokens[0].balance = 1_000_000_000_000;
+        pool.tokens[0].weight = 800_000_000;
+        pool.tokens[1].balance = 100_000_000_000;
+        pool.tokens[1].weight = 200_000_000;
+
+        // (1000 / 0.8) / (100 / 0.2)
+        assert_eq!(pool.get_spot_price(0, 1), Some(2_500_000_000));
+        assert_eq!(pool.get_spot_price(1, 0), Some(400_000_000));
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(pool.get_spot_price(0, 1), Some(2_500_000_000_000));
+        assert_eq!(pool.get_spot_price(1, 0), Some(400_000));
+
+        pool.tokens[1].balance = 0;
+        assert_eq!(pool.get_spot_price(0, 1), None);
+        assert_eq!(pool.get_spot_price(1, 0), Some(0));
+    }
+
+    #[test]
+    fn test_quote_remove_liquidity_proportional() {
+        let mut pool = pool();
+        let pool_token_supply = 2 * BALANCE;
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply, pool_token_supply),
+            Some(vec![BALANCE, BALANCE])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(0, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(1, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(pool.quote_remove_liquidity_proportional(BALANCE, 0), None);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10_000])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(2_000, pool_token_supply),
+            Some(vec![1_000, 1])
+        );
+        assert_eq!(
+            pool.quote_remove_liquidity_proportional(1_998, pool_token_supply),
+            Some(vec![999, 0])
+        );
+    }
+
+    #[test]
+    fn test_quote_add_liquidity() {
+        let mut pool = pool();
+        pool.swap_fee = 10_000_000;
+        pool.tokens[0].balance = 5_000_000_000_000_000_000;
+        pool.tokens[1].balance = 1_000_000_000_000_000_000;
+
+        let amounts_in = vec![5_000_000_000_000_000 >> 1, 1_000_000_000_000_000 >> 1];
+        assert_eq!(
+            pool.quote_add_liquidity(&amounts_in, 2236021719197214567 << 1),
+            Some(2236012775110337)
+        );
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        let amounts_in = vec![5_000_000_000_000_000 >> 1, 1_000_000_000_000 >> 1];
+        assert_eq!(
+            pool.quote_add_liquidity(&amounts_in, 2236021719197214567 << 1),
+            Some(2236012775110337)
+        );
+
+        assert_eq!(
+            pool.quote_add_liquidity(&[1_000_000_000], 2236021719197214567 << 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_quote_remove_liquidity_single() {
+        let mut pool = pool();
+        pool.swap_fee = 10_000_000;
+        pool.tokens[0].balance = 5_000_000_000_000_000_000;
+        pool.tokens[1].balance = 1_000_000_000_000_000_000;
+
+        let pool_token_supply = 2236021719197214567 << 1;
+        let (amount_out, amount_fee) = pool
+            .quote_remove_liquidity_single(0, 2222605588882, pool_token_supply)
+            .unwrap();
+        assert_eq!(amount_out, 4930225000000);
+        assert!(amount_fee > 0);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        let (amount_out, _) = pool
+            .quote_remove_liquidity_single(1, 2222605588882, pool_token_supply)
+            .unwrap();
+        assert_eq!(amount_out, 986045000);
+
+        // Cannot go below the minimum invariant ratio
+        assert!(pool
+            .quote_remove_liquidity_single(0, pool_token_supply / 100 * 30, pool_token_supply)
+            .is_some());
+        assert!(pool
+            .quote_remove_liquidity_single(0, pool_token_supply / 100 * 31, pool_token_supply)
+            .is_none());
+    }
+
+    #[test]
+    fn test_get_swap_result_invalid() {
+        let pool = pool();
+
+        assert_eq!(pool.get_token_index(pool.tokens[1].mint), Some(1));
+        assert_eq!(pool.get_token_index(Pubkey::new_unique()), None);
+
+        // Cannot exceed maximum in ratio
+        assert!(pool.get_swap_result(0, 1, 300_000_000_000, 0).is_some());
+        assert!(pool.get_swap_result(0, 1, 300_000_000_001, 0).is_none());
+    }
+
+    #[test]
+    fn test_get_swap_result_exact_out() {
+        let pool = pool();
+
+        let (amount_out, _) = pool.get_swap_result(0, 1, 1_000_000_000, 0).unwrap();
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, amount_out, 0).unwrap();
+        // The fee is charged on the amount in rather than the amount out, so it comes out slightly lower
+        assert_eq!(amount_out, 998_001_000);
+        assert_eq!(amount_in, 999_998_999);
+        assert_eq!(amount_fee, 999_999);
+
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, 1_000_000_000, 0).unwrap();
+        let amount_out = weighted_math::calc_out_given_in(
+            pool.tokens[0].balance,
+            pool.tokens[0].weight,
+            pool.tokens[1].balance,
+            pool.tokens[1].weight,
+            amount_in - amount_fee,
+        )
+        .unwrap();
+        assert!(amount_out >= 1_000_000_000);
+        assert_eq!(amount_fee, amount_in.mul_up(pool.swap_fee));
+
+        // Cannot exceed maximum out ratio
+        assert!(pool.get_swap_result_exact_out(0, 1, 300_000_000_000, 0).is_some());
+        assert!(pool.get_swap_result_exact_out(0, 1, 300_000_000_001, 0).is_none());
+    }
+
+    #[test]
+    fn test_get_swap_result_x_amount() {
+        let pool = pool();
+        // 50% discount on the swap fee
+        let x_amount = 2_000_000_000_000_000;
+
+        let (_, amount_fee) = pool.get_swap_result(0, 1, 1_000_000_000, 0).unwrap();
+        let (_, discounted_amount_fee) = pool.get_swap_result(0, 1, 1_000_000_000, x_amount).unwrap();
+        assert_eq!(amount_fee, 999000);
+        assert_eq!(discounted_amount_fee, 499500);
+
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, 1_000_000_000, 0).unwrap();
+        let (discounted_amount_in, discounted_amount_fee) =
+            pool.get_swap_result_exact_out(0, 1, 1_000_000_000, x_amount).unwrap();
+        assert_eq!(amount_fee, 1002005);
+        assert_eq!(discounted_amount_fee, 500752);
+        assert_eq!(amount_in - amount_fee, discounted_amount_in - discounted_amount_fee);
+
+        // the swap fee is fully discounted from the last tier on
+        let x_amount = 51_200_000_000_000_000;
+        let (amount_out, amount_fee) = pool.get_swap_result(0, 1, 1_000_000_000, x_amount).unwrap();
+        assert_eq!(amount_fee, 0);
+        assert_eq!(
+            Some(amount_out),
+            weighted_math::calc_out_given_in(
+                pool.tokens[0].balance,
+                pool.tokens[0].weight,
+                pool.tokens[1].balance,
+                pool.tokens[1].weight,
+                1_000_000_000,
+            )
+            .ok()
+        );
+        let (amount_in, amount_fee) = pool.get_swap_result_exact_out(0, 1, amount_out, x_amount).unwrap();
+        assert_eq!(amount_fee, 0);
+        assert!(amount_in <= 1_000_000_000);
+    }
+
+    #[test]
+    fn test_clamp_balance_to_vault() {
+        let mut pool = pool();
+
+        // the vault holds the reserves of other pools as well
+        pool.clamp_balance_to_vault(0, 5_000_000_000_000);
+        assert_eq!(pool.tokens[0].balance, 1_000_000_000_000);
+        pool.clamp_balance_to_vault(0, 1_000_000_000_000);
+        assert_eq!(pool.tokens[0].balance, 1_000_000_000_000);
+        pool.clamp_balance_to_vault(0, 999_999_999_999);
+        assert_eq!(pool.tokens[0].balance, 999_999_999_999);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        pool.clamp_balance_to_vault(1, u64::MAX);
+        assert_eq!(pool.tokens[1].balance, 1_000_000_000_000);
+        pool.clamp_balance_to_vault(1, 1_000_000_000);
+        assert_eq!(pool.tokens[1].balance, 1_000_000_000_000);
+        pool.clamp_balance_to_vault(1, 999_999_999);
+        assert_eq!(pool.tokens[1].balance, 999_999_999_000);
+    }
+
+    #[test]
+    fn test_calc_transfer_fee() {
+        let mut pool = pool();
+        assert_eq!(pool.calc_transfer_fee(1_000_000, 0), 0);
+        assert_eq!(pool.calc_pre_transfer_fee_amount(1_000_000, 0), Some(1_000_000));
+
+        // 1% capped at 5_000
+        pool.tokens[0].transfer_fee_bps = Some(100);
+        pool.tokens[0].max_transfer_fee = 5_000;
+        assert_eq!(pool.calc_transfer_fee(0, 0), 0);
+        assert_eq!(pool.calc_transfer_fee(1, 0), 1);
+        assert_eq!(pool.calc_transfer_fee(100_000, 0), 1_000);
+        assert_eq!(pool.calc_transfer_fee(100_001, 0), 1_001);
+        assert_eq!(pool.calc_transfer_fee(1_000_000, 0), 5_000);
+        assert_eq!(pool.calc_transfer_fee(u64::MAX, 0), 5_000);
+
+        assert_eq!(pool.calc_pre_transfer_fee_amount(0, 0), Some(0));
+        assert_eq!(pool.calc_pre_transfer_fee_amount(99_000, 0), Some(100_000));
+        assert_eq!(pool.calc_pre_transfer_fee_amount(995_000, 0), Some(1_000_000));
+        assert_eq!(pool.calc_pre_transfer_fee_amount(u64::MAX - 5_000, 0), Some(u64::MAX));
+        assert_eq!(pool.calc_pre_transfer_fee_amount(u64::MAX, 0), None);
+        for amount in [1, 99, 12_345, 99_001, 494_999, 10_000_000] {
+            let pre_fee_amount = pool.calc_pre_transfer_fee_amount(amount, 0).unwrap();
+            assert_eq!(pre_fee_amount - pool.calc_transfer_fee(pre_fee_amount, 0), amount);
+        }
+
+        // a zero fee extension doesn't charge anything
+        pool.tokens[0].transfer_fee_bps = Some(0);
+        assert_eq!(pool.calc_transfer_fee(1_000_000, 0), 0);
+        assert_eq!(pool.calc_pre_transfer_fee_amount(1_000_000, 0), Some(1_000_000));
+
+        // the whole amount is withheld up to the maximum fee
+        pool.tokens[0].transfer_fee_bps = Some(MAX_FEE_BASIS_POINTS);
+        assert_eq!(pool.calc_transfer_fee(1_000, 0), 1_000);
+        assert_eq!(pool.calc_transfer_fee(1_000_000, 0), 5_000);
+        assert_eq!(pool.calc_pre_transfer_fee_amount(1_000, 0), Some(6_000));
+    }
+
+    #[test]
+    fn test_get_swap_result_transfer_fee() {
+        let amount_in = 1_000_000_000;
+        let (amount_out, amount_fee) = pool().get_swap_result(0, 1, amount_in, 0).unwrap();
+        let (exact_amount_in, exact_amount_fee) = pool().get_swap_result_exact_out(0, 1, amount_out, 0).unwrap();
+
+        // a zero fee extension quotes the same as no extension
+        let fee_pool = pool_with_transfer_fees(0, 0);
+        assert_eq!(
+            fee_pool.get_swap_result(0, 1, amount_in, 0),
+            Some((amount_out, amount_fee))
+        );
+        assert_eq!(
+            fee_pool.get_swap_result_exact_out(0, 1, amount_out, 0),
+            Some((exact_amount_in, exact_amount_fee))
+        );
+
+        // 1% on the way in, 0.5% on the way out
+        let fee_pool = pool_with_transfer_fees(100, 50);
+        let (gross_amount_out, _) = pool().get_swap_result(0, 1, 990_000_000, 0).unwrap();
+        let (amount_out, _) = fee_pool.get_swap_result(0, 1, amount_in, 0).unwrap();
+        assert_eq!(amount_out, gross_amount_out - gross_amount_out.mul_up(5_000_000));
+
+        let (net_amount_in, _) = pool().get_swap_result_exact_out(0, 1, gross_amount_out, 0).unwrap();
+        let (exact_amount_in, _) = fee_pool.get_swap_result_exact_out(0, 1, amount_out, 0).unwrap();
+        assert_eq!(
+            exact_amount_in,
+            fee_pool.calc_pre_transfer_fee_amount(net_amount_in, 0).unwrap()
+        );
+    }
+
+    fn pool_with_transfer_fees(transfer_fee_bps_in: u16, transfer_fee_bps_out: u16) -> Pool {
+        let mut pool = pool();
+        pool.tokens[0].transfer_fee_bps = Some(transfer_fee_bps_in);
+        pool.tokens[0].max_transfer_fee = u64::MAX;
+        pool.tokens[1].transfer_fee_bps = Some(transfer_fee_bps_out);
+        pool.tokens[1].max_transfer_fee = u64::MAX;
+        pool
+    }
+
+    #[test]
+    fn test_transfer_fee_not_serialized() {
+        let mut pool = pool();
+        let mut data = vec![];
+        pool.try_serialize(&mut data).unwrap();
+
+        pool.tokens[0].transfer_fee_bps = Some(100);
+        pool.tokens[0].max_transfer_fee = 5_000;
+        let mut data_with_transfer_fee = vec![];
+        pool.try_serialize(&mut data_with_transfer_fee).unwrap();
+        assert_eq!(data_with_transfer_fee, data);
+
+        let pool = Pool::try_deserialize(&mut &data_with_transfer_fee[..]).unwrap();
+        assert_eq!(pool.tokens[0].transfer_fee_bps, None);
+        assert_eq!(pool.tokens[0].max_transfer_fee, 0);
+    }
+
+    #[test]
+    fn test_try_deserialize_layout() {
+        let owner = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let pending_owner = Pubkey::new_unique();
+
+        let mut data = Pool::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(vault.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(&[254, 1]);
+        data.extend_from_slice(&2_000_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&3_000_000u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for (token_mint, weight) in token_mints.iter().zip([800_000_000u64, 200_000_000]) {
+            data.extend_from_slice(token_mint.as_ref());
+            data.extend_from_slice(&[6, 1]);
+            data.extend_from_slice(&1_000u64.to_le_bytes());
+            data.extend_from_slice(&5_000_000_000_000u64.to_le_bytes());
+            data.extend_from_slice(&weight.to_le_bytes());
+        }
+        data.push(1);
+        data.extend_from_slice(pending_owner.as_ref());
+
+        let pool = Pool::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(pool.owner, owner);
+        assert_eq!(pool.vault, vault);
+        assert_eq!(pool.mint, mint);
+        assert_eq!(pool.authority_bump, 254);
+        assert!(pool.is_active);
+        assert_eq!(pool.invariant, 2_000_000_000_000);
+        assert_eq!(pool.swap_fee, 3_000_000);
+        assert_eq!(pool.tokens.len(), 2);
+        for (token, (token_mint, weight)) in pool
+            .tokens
+            .iter()
+            .zip(token_mints.iter().zip([800_000_000, 200_000_000]))
+        {
+            assert_eq!(token.mint, *token_mint);
+            assert_eq!(token.decimals, 6);
+            assert!(token.scaling_up);
+            assert_eq!(token.scaling_factor, 1_000);
+            assert_eq!(token.balance, 5_000_000_000_000);
+            assert_eq!(token.weight, weight);
+        }
+        assert_eq!(pool.pending_owner, Some(pending_owner));
+
+        // no pending owner
+        let data = [&data[..data.len() - 33], &[0]].concat();
+        assert_eq!(Pool::try_deserialize(&mut &data[..]).unwrap().pending_owner, None);
+    }
+
+    #[test]
+    fn test_validate_weights() {
+        let mut pool = pool();
+        assert_eq!(pool.validate_weights(), Ok(()));
+
+        pool.tokens[0].weight = 800_000_000;
+        pool.tokens[1].weight = 200_000_000;
+        assert_eq!(pool.validate_weights(), Ok(()));
+
+        // 70/20 doesn't sum to ONE
+        pool.tokens[0].weight = 700_000_000;
+        assert_eq!(pool.validate_weights(), Err(WeightedMathError::InvalidWeights));
+
+        // rounding within the tolerance
+        pool.tokens[0].weight = 800_001_000;
+        assert_eq!(pool.validate_weights(), Ok(()));
+        pool.tokens[0].weight = 800_001_001;
+        assert_eq!(pool.validate_weights(), Err(WeightedMathError::InvalidWeights));
+
+        // 95/5 sums to ONE but is out of the weight range
+        pool.tokens[0].weight = 950_000_000;
+        pool.tokens[1].weight = 50_000_000;
+        assert_eq!(pool.validate_weights(), Err(WeightedMathError::InvalidWeights));
+
+        pool.tokens[0].weight = ONE;
+        pool.tokens[1].weight = 0;
+        assert_eq!(pool.validate_weights(), Err(WeightedMathError::InvalidWeights));
+
+        // 40/30/30
+        pool.tokens.push(PoolToken {
+            mint: Pubkey::new_unique(),
+            ..pool.tokens[0]
+        });
+        pool.tokens[0].weight = 400_000_000;
+        pool.tokens[1].weight = 300_000_000;
+        pool.tokens[2].weight = 300_000_000;
+        assert_eq!(pool.validate_weights(), Ok(()));
+    }
+
+    #[test]
+    fn test_swap_fee_decimal() {
+        let mut state = pool();
+        for (swap_fee, expected) in [
+            (1_000_000, "0.001"),
+            (10_000, "0.00001"),
+            (25_000_000, "0.025"),
+            (1_000, "0.000001"),
+            (0, "0"),
+            (ONE, "1"),
+        ] {
+            state.swap_fee = swap_fee;
+            assert_eq!(state.swap_fee_decimal(), Decimal::from_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(pool().validate(), Ok(()));
+
+        let mut state = pool();
+        state.tokens.truncate(1);
+        state.tokens[0].weight = ONE;
+        assert_eq!(state.validate(), Err(PoolError::InvalidTokenCount));
+
+        let mut state = pool();
+        let token = state.tokens[0];
+        state.tokens = (0..=weighted_math::MAX_TOKENS)
+            .map(|_| PoolToken {
+                mint: Pubkey::new_unique(),
+                ..token
+            })
+            .collect();
+        assert_eq!(state.validate(), Err(PoolError::InvalidTokenCount));
+
+        for swap_fee in [0, weighted_math::MIN_SWAP_FEE - 1, weighted_math::MAX_SWAP_FEE + 1] {
+            let mut state = pool();
+            state.swap_fee = swap_fee;
+            assert_eq!(state.validate(), Err(PoolError::InvalidSwapFee));
+        }
+        for swap_fee in [weighted_math::MIN_SWAP_FEE, weighted_math::MAX_SWAP_FEE] {
+            let mut state = pool();
+            state.swap_fee = swap_fee;
+            assert_eq!(state.validate(), Ok(()));
+        }
+
+        let mut state = pool();
+        state.tokens[0].weight = 600_000_000;
+        assert_eq!(state.validate(), Err(PoolError::InvalidWeights));
+        state.tokens[1].weight = 400_000_000;
+        assert_eq!(state.validate(), Ok(()));
+        state.tokens[0].weight = weighted_math::MAX_WEIGHT + 50_000_000;
+        state.tokens[1].weight = weighted_math::MIN_WEIGHT - 50_000_000;
+        assert_eq!(state.validate(), Err(PoolError::InvalidWeights));
+
+        let mut state = pool();
+        state.tokens[1].scaling_factor = 999;
+        assert_eq!(state.validate(), Err(PoolError::InvalidScalingFactor));
+
+        let mut state = pool();
+        state.tokens[1].mint = state.tokens[0].mint;
+        assert_eq!(state.validate(), Err(PoolError::DuplicateMint));
+    }
+
+    #[test]
+    fn test_validate_scaling() {
+        let mut pool = pool();
+        assert_eq!(pool.validate_scaling(), Ok(()));
+
+        for scaling_factor in [10, 1_000, 1_000_000_000, 10_000_000_000_000_000_000] {
+            pool.tokens[1].scaling_factor = scaling_factor;
+            assert_eq!(pool.validate_scaling(), Ok(()));
+        }
+        for scaling_factor in [0, 2, 999, 1_001, 5_000, u64::MAX] {
+            pool.tokens[1].scaling_factor = scaling_factor;
+            assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+        }
+
+        // zero is rejected either way it scales
+        pool.tokens[1].scaling_factor = 1;
+        pool.tokens[0].scaling_factor = 0;
+        pool.tokens[0].scaling_up = false;
+        assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+        pool.tokens[0].scaling_up = true;
+        assert_eq!(pool.validate_scaling(), Err(ScalingError::InvalidScalingFactor));
+    }
+
+    #[test]
+    fn test_quote_add_liquidity_proportional() {
+        let mut pool = pool();
+        let pool_token_supply = 2 * BALANCE;
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(0, pool_token_supply),
+            Some(vec![0, 0])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(1, pool_token_supply),
+            Some(vec![1, 1])
+        );
+
+        // the initial deposit is up to the caller
+        assert_eq!(pool.quote_add_liquidity_proportional(BALANCE, 0), None);
+
+        // 6 decimals token scaled up to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(pool_token_supply / 10, pool_token_supply),
+            Some(vec![BALANCE / 10, BALANCE / 10_000])
+        );
+        assert_eq!(
+            pool.quote_add_liquidity_proportional(1, pool_token_supply),
+            Some(vec![1, 1])
+        );
+    }
+
+    #[test]
+    fn test_get_reserves() {
+        let mut pool = pool();
+        pool.tokens.push(PoolToken {
+            mint: Pubkey::new_unique(),
+            ..pool.tokens[0]
+        });
+        assert_eq!(pool.get_reserves(), pool.get_balances());
+
+        // 6 decimals token scaled up and 12 decimals token scaled down to the 9 decimals wrapped balance
+        pool.tokens[1].decimals = 6;
+        pool.tokens[1].scaling_factor = 1_000;
+        pool.tokens[2].decimals = 12;
+        pool.tokens[2].scaling_up = false;
+        pool.tokens[2].scaling_factor = 1_000;
+        assert_eq!(pool.get_reserves(), [BALANCE, BALANCE / 1_000, BALANCE * 1_000]);
+        assert_eq!(pool.get_balances(), [BALANCE; 3]);
+    }
+
+    #[test]
+    fn test_try_deserialize_truncated() {
+        let data = pool_data();
+        assert!(Pool::try_deserialize(&mut &data[..]).is_ok());
+
+        // Cut off in the middle of the last token, right before `pending_owner`
+        let truncated = &data[..data.len() - 21];
+        assert_eq!(
+            Pool::try_deserialize(&mut &truncated[..]).err(),
+            Some(ErrorCode::AccountDidNotDeserialize.into())
+        );
+        assert_eq!(
+            Pool::try_deserialize(&mut &data[..8]).err(),
+            Some(ErrorCode::AccountDidNotDeserialize.into())
+        );
+        assert_eq!(
+            Pool::try_deserialize(&mut &data[..4]).err(),
+            Some(ErrorCode::AccountDiscriminatorNotFound.into())
+        );
+    }
+
+    #[test]
+    fn test_try_serialize_round_trip() {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for num_tokens in 2..=weighted_math::MAX_TOKENS {
+            let mut pool = pool();
+            pool.is_active = next() % 2 == 0;
+            pool.invariant = next();
+            pool.swap_fee = next();
+            pool.pending_owner = (next() % 2 == 0).then(Pubkey::new_unique);
+            pool.tokens = (0..num_tokens)
+                .map(|_| PoolToken {
+                    mint: Pubkey::new_unique(),
+                    decimals: next() as u8,
+                    scaling_up: next() % 2 == 0,
+                    scaling_factor: next(),
+                    balance: next(),
+                    weight: next(),
+                    transfer_fee_bps: None,
+                    max_transfer_fee: 0,
+                })
+                .collect();
+
+            let mut data = vec![];
+            pool.try_serialize(&mut data).unwrap();
+            assert_eq!(Pool::try_deserialize(&mut &data[..]).unwrap(), pool);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut pool = pool();
+        pool.pending_owner = Some(Pubkey::new_unique());
+
+        let json = serde_json::to_value(&pool).unwrap();
+        assert_eq!(json["owner"], pool.owner.to_string());
+        assert_eq!(json["pending_owner"], pool.pending_owner.unwrap().to_string());
+        assert_eq!(json["tokens"][1]["mint"], pool.tokens[1].mint.to_string());
+        assert_eq!(json["tokens"][1]["balance"], BALANCE);
+        assert_eq!(serde_json::from_value::<Pool>(json).unwrap(), pool);
+
+        pool.pending_owner = None;
+        let json = serde_json::to_string(&pool).unwrap();
+        assert_eq!(serde_json::from_str::<Pool>(&json).unwrap(), pool);
+
+        // transfer fees are off-chain only, so snapshots without them still load
+        let mut json = serde_json::to_value(&pool).unwrap();
+        json["tokens"][0].as_object_mut().unwrap().remove("transfer_fee_bps");
+        json["tokens"][0].as_object_mut().unwrap().remove("max_transfer_fee");
+        assert_eq!(serde_json::from_value::<Pool>(json).unwrap(), pool);
+    }
+
+    #[test]
+    fn test_decimals_of() {
+        let mut pool = pool();
+        pool.tokens[1].decimals = 6;
+
+        assert_eq!(pool.decimals_of(&pool.tokens[0].mint), Some(9));
+        assert_eq!(pool.decimals_of(&pool.tokens[1].mint), Some(6));
+        assert_eq!(pool.decimals_of(&Pubkey::new_unique()), None);
     }
 }
@@ -0,0 +1,126 @@
+//! browser bindings for quoting, amounts cross the boundary as decimal strings since JS numbers lose u64 precision
+
+use crate::pool::Pool;
+use crate::{PoolHealth, WeightedSwap};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::AccountDeserialize;
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::{Amm, ClockRef, QuoteParams, SwapMode};
+use math::fixed_math::ONE;
+use stabble_vault::vault::Vault;
+use std::str::FromStr;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeightedQuote {
+    pub in_amount: String,
+    pub out_amount: String,
+    pub fee_amount: String,
+    pub fee_pct: String,
+}
+
+/// exact in quote from raw pool and vault account data
+#[wasm_bindgen]
+pub fn quote_weighted(
+    pool_bytes: &[u8],
+    vault_bytes: &[u8],
+    in_mint: &str,
+    out_mint: &str,
+    amount: &str,
+) -> Result<JsValue, JsError> {
+    quote_exact_in(pool_bytes, vault_bytes, in_mint, out_mint, amount)
+        .map(JsValue::from)
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn quote_exact_in(
+    pool_bytes: &[u8],
+    vault_bytes: &[u8],
+    in_mint: &str,
+    out_mint: &str,
+    amount: &str,
+) -> Result<WeightedQuote> {
+    let state = Pool::try_deserialize(&mut &pool_bytes[..])?;
+    state.validate_scaling()?;
+    state.validate_weights()?;
+    let vault = Vault::try_deserialize(&mut &vault_bytes[..])?;
+    if vault.beneficiary_fee >= ONE {
+        return Err(anyhow!(
+            "beneficiary fee {} is not below {}",
+            vault.beneficiary_fee,
+            ONE
+        ));
+    }
+    // transfer fees are read from the mints on update, which the browser doesn't load
+    let token_programs = vec![spl_token::ID; state.tokens.len()];
+    let health = PoolHealth::of(&state);
+
+    let weighted_swap = WeightedSwap {
+        key: Pubkey::default(),
+        state: Arc::new(state),
+        beneficiary: Some(vault.beneficiary),
+        beneficiary_fee: vault.beneficiary_fee,
+        clock_ref: ClockRef::default(),
+        is_vault_active: vault.is_active,
+        token_programs,
+        health,
+    };
+    let quote = weighted_swap.quote(&QuoteParams {
+        amount: u64::from_str(amount)?,
+        input_mint: Pubkey::from_str(in_mint)?,
+        output_mint: Pubkey::from_str(out_mint)?,
+        swap_mode: SwapMode::ExactIn,
+    })?;
+
+    Ok(WeightedQuote {
+        in_amount: quote.in_amount.to_string(),
+        out_amount: quote.out_amount.to_string(),
+        fee_amount: quote.fee_amount.to_string(),
+        fee_pct: quote.fee_pct.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::tests::pool;
+    use anchor_lang::AccountSerialize;
+
+    #[test]
+    fn test_quote_exact_in() {
+        let pool = pool();
+        let mut pool_bytes = vec![];
+        pool.try_serialize(&mut pool_bytes).unwrap();
+        let vault = Vault {
+            admin: Pubkey::new_unique(),
+            withdraw_authority: Pubkey::new_unique(),
+            withdraw_authority_bump: 255,
+            authority_bump: 255,
+            is_active: true,
+            beneficiary: Pubkey::new_unique(),
+            beneficiary_fee: 0,
+            pending_admin: None,
+        };
+        let mut vault_bytes = vec![];
+        vault.try_serialize(&mut vault_bytes).unwrap();
+        let in_mint = pool.tokens[0].mint.to_string();
+        let out_mint = pool.tokens[1].mint.to_string();
+
+        let (amount_out, amount_fee) = pool.get_swap_result(0, 1, 1_000_000_000, 0).unwrap();
+        assert_eq!(
+            quote_exact_in(&pool_bytes, &vault_bytes, &in_mint, &out_mint, "1000000000").unwrap(),
+            WeightedQuote {
+                in_amount: String::from("1000000000"),
+                out_amount: amount_out.to_string(),
+                fee_amount: amount_fee.to_string(),
+                fee_pct: String::from("0.001000000"),
+            }
+        );
+
+        assert!(quote_exact_in(&pool_bytes, &vault_bytes, &in_mint, &out_mint, "-1").is_err());
+        assert!(quote_exact_in(&pool_bytes, &vault_bytes, &in_mint, "not a mint", "1").is_err());
+        assert!(quote_exact_in(&vault_bytes, &pool_bytes, &in_mint, &out_mint, "1").is_err());
+    }
+}